@@ -0,0 +1,350 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use lru::LruCache;
+
+/// How long an album is assumed to keep the same picture before asking MPD
+/// again.
+const ALBUM_TTL: Duration = Duration::from_secs(3600);
+
+const ALBUM_CAPACITY: NonZeroUsize = match NonZeroUsize::new(4096) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+/// In MiB, if `EMPEDE_ART_CACHE_SIZE` is not set.
+const DEFAULT_MEMORY_SIZE: usize = 32;
+
+/// In MiB, if `EMPEDE_ART_DISK_CACHE_SIZE` is not set.
+const DEFAULT_DISK_SIZE: u64 = 256;
+
+/// Pictures by album, in memory and in the cache directory if there is one.
+///
+/// Albums map to the hash of their picture, and the renditions of each
+/// picture are stored under a name derived from that hash (see
+/// [`super::Rendition::name`]), so albums sharing a picture share its
+/// renditions too.
+pub struct Cache {
+    dir: Option<PathBuf>,
+    memory: Mutex<Memory>,
+    /// For unique names of files being written
+    writes: AtomicU64,
+    /// The size of the cache directory, as far as known. It starts out above
+    /// the maximum, so that the first write prunes and counts what is there.
+    disk_size: AtomicU64,
+    max_disk_size: u64,
+    pruning: AtomicBool,
+}
+
+struct Memory {
+    /// The hash of each album's picture, `None` if it has none, and when it
+    /// was looked up
+    albums: LruCache<String, (Option<String>, Instant)>,
+    pictures: LruCache<String, Arc<[u8]>>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Cache {
+    /// The cache shared by all servers, configured by `EMPEDE_CACHE_DIR`,
+    /// `EMPEDE_ART_CACHE_SIZE` and `EMPEDE_ART_DISK_CACHE_SIZE`.
+    pub fn global() -> &'static Cache {
+        static CACHE: OnceLock<Cache> = OnceLock::new();
+        CACHE.get_or_init(Self::from_env)
+    }
+
+    fn from_env() -> Self {
+        let dir = match std::env::var("EMPEDE_CACHE_DIR") {
+            Ok(dir) if dir.is_empty() => None,
+            Ok(dir) => Some(PathBuf::from(dir)),
+            Err(_) => std::env::var("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .ok()
+                .map(|cache| cache.join("empede")),
+        };
+
+        let dir = dir.filter(|dir| match std::fs::create_dir_all(dir.join("albums")) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("Not caching album art in {} ({e})", dir.display());
+                false
+            }
+        });
+
+        let max_size = std::env::var("EMPEDE_ART_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_MEMORY_SIZE);
+        let max_disk_size = std::env::var("EMPEDE_ART_DISK_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_DISK_SIZE)
+            * 1024
+            * 1024;
+
+        Self {
+            dir,
+            memory: Mutex::new(Memory {
+                albums: LruCache::new(ALBUM_CAPACITY),
+                pictures: LruCache::unbounded(),
+                size: 0,
+                max_size: max_size * 1024 * 1024,
+            }),
+            writes: AtomicU64::new(0),
+            disk_size: AtomicU64::new(max_disk_size + 1),
+            max_disk_size,
+            pruning: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the hash of the album's picture, `Some(None)` if it is known
+    /// to have none, or `None` if it has to be looked up.
+    pub async fn album(&self, album: &str) -> Option<Option<String>> {
+        if let Some((hash, fetched)) = self.memory.lock().unwrap().albums.get(album) {
+            if fetched.elapsed() < ALBUM_TTL {
+                return Some(hash.clone());
+            }
+        }
+
+        let path = self
+            .dir
+            .as_ref()?
+            .join("albums")
+            .join(super::hash(album.as_bytes()));
+        let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age >= ALBUM_TTL {
+            return None;
+        }
+
+        let hash = tokio::fs::read_to_string(&path).await.ok()?;
+        let fetched = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+        self.memory
+            .lock()
+            .unwrap()
+            .albums
+            .put(album.to_string(), (Some(hash.clone()), fetched));
+        Some(Some(hash))
+    }
+
+    pub async fn set_album(&self, album: &str, hash: Option<&str>) {
+        self.memory.lock().unwrap().albums.put(
+            album.to_string(),
+            (hash.map(str::to_string), Instant::now()),
+        );
+
+        // Albums without a picture are only remembered in memory
+        if let Some(hash) = hash {
+            let name = format!("albums/{}", super::hash(album.as_bytes()));
+            self.write(&name, hash.as_bytes()).await;
+        }
+    }
+
+    pub async fn picture(&self, name: &str) -> Option<Arc<[u8]>> {
+        if let Some(data) = self.memory.lock().unwrap().pictures.get(name) {
+            return Some(data.clone());
+        }
+
+        let path = self.dir.as_ref()?.join(name);
+        let data: Arc<[u8]> = tokio::fs::read(&path).await.ok()?.into();
+        // Pruning goes by modification time, so pictures in use are kept
+        let _ = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(SystemTime::now())
+        })
+        .await;
+        self.memory.lock().unwrap().insert(name, data.clone());
+        Some(data)
+    }
+
+    pub async fn set_picture(&self, name: &str, data: Arc<[u8]>) {
+        self.memory.lock().unwrap().insert(name, data.clone());
+        self.write(name, &data).await;
+    }
+
+    /// Writes a file to the cache directory, through a temporary file so
+    /// concurrent readers never see it partially written, and prunes the
+    /// directory if that made it too large.
+    async fn write(&self, name: &str, data: &[u8]) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        let path = dir.join(name);
+        let temporary = dir.join(format!(
+            ".{}.{}",
+            std::process::id(),
+            self.writes.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let result = match tokio::fs::write(&temporary, data).await {
+            Ok(()) => tokio::fs::rename(&temporary, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            println!("Could not cache album art in {} ({e})", path.display());
+            let _ = tokio::fs::remove_file(&temporary).await;
+            return;
+        }
+
+        let size = self
+            .disk_size
+            .fetch_add(data.len() as u64, Ordering::Relaxed)
+            .saturating_add(data.len() as u64);
+        if size > self.max_disk_size && !self.pruning.swap(true, Ordering::Acquire) {
+            let (dir, max_size) = (dir.clone(), self.max_disk_size);
+            match tokio::task::spawn_blocking(move || prune(&dir, max_size)).await {
+                Ok(size) => self.disk_size.store(size, Ordering::Relaxed),
+                Err(e) => println!("Could not prune the album art cache ({e})"),
+            }
+            self.pruning.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Removes the least recently used files from the cache directory until it
+/// is well within `max_size` bytes, so it is not pruned on every write, and
+/// returns the size left.
+fn prune(dir: &Path, max_size: u64) -> u64 {
+    let mut files = Vec::new();
+    for dir in [dir.to_path_buf(), dir.join("albums")] {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Temporary files are still being written
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((modified, metadata.len(), entry.path()));
+            }
+        }
+    }
+
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_unstable_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in files {
+        if size <= max_size / 10 * 9 {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            size -= len;
+        }
+    }
+    size
+}
+
+impl Memory {
+    /// Inserts a picture, evicting the least recently used ones to stay
+    /// within the maximum size.
+    fn insert(&mut self, name: &str, data: Arc<[u8]>) {
+        if data.len() > self.max_size {
+            return;
+        }
+
+        self.size += data.len();
+        if let Some(replaced) = self.pictures.put(name.to_string(), data) {
+            self.size -= replaced.len();
+        }
+
+        while self.size > self.max_size {
+            let Some((_, evicted)) = self.pictures.pop_lru() else {
+                break;
+            };
+            self.size -= evicted.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_least_recently_used_files() {
+        let dir = std::env::temp_dir().join(format!("empede-cache-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("albums")).unwrap();
+
+        let now = SystemTime::now();
+        for (name, age) in [("old", 30), ("albums/older", 40), ("new", 10), ("newer", 0)] {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            file.set_len(100).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        std::fs::write(dir.join(".1.0"), [0; 100]).unwrap();
+
+        assert_eq!(prune(&dir, 450), 400);
+        assert_eq!(prune(&dir, 300), 200);
+        assert!(!dir.join("albums/older").exists());
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("new").exists() && dir.join("newer").exists());
+        assert!(dir.join(".1.0").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn memory(max_size: usize) -> Memory {
+        Memory {
+            albums: LruCache::new(ALBUM_CAPACITY),
+            pictures: LruCache::unbounded(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    fn picture(len: usize) -> Arc<[u8]> {
+        vec![0; len].into()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_pictures() {
+        let mut memory = memory(100);
+        memory.insert("a", picture(40));
+        memory.insert("b", picture(40));
+        memory.pictures.get("a");
+        memory.insert("c", picture(40));
+
+        assert_eq!(memory.size, 80);
+        assert!(memory.pictures.contains("a") && memory.pictures.contains("c"));
+        assert!(!memory.pictures.contains("b"));
+    }
+
+    #[test]
+    fn counts_replaced_pictures_once() {
+        let mut memory = memory(100);
+        memory.insert("a", picture(40));
+        memory.insert("a", picture(60));
+        assert_eq!(memory.size, 60);
+        assert_eq!(memory.pictures.len(), 1);
+    }
+
+    #[test]
+    fn skips_pictures_larger_than_the_cache() {
+        let mut memory = memory(100);
+        memory.insert("a", picture(40));
+        memory.insert("b", picture(101));
+        assert_eq!(memory.size, 40);
+        assert!(memory.pictures.contains("a") && !memory.pictures.contains("b"));
+
+        memory.insert("c", picture(100));
+        assert_eq!(memory.size, 100);
+        assert_eq!(memory.pictures.len(), 1);
+    }
+}
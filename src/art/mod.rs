@@ -0,0 +1,194 @@
+//! Album art, downscaled and re-encoded as JPEG for the size it is displayed
+//! at, and cached so each picture only has to be found once.
+
+mod cache;
+mod resolver;
+
+use std::io::Cursor;
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageResult};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+
+pub use cache::Cache;
+pub use resolver::{Resolver, PLACEHOLDER};
+
+/// Requested sizes are rounded up to a multiple of this, so slightly
+/// different sizes share cache entries.
+const SIZE_STEP: u32 = 32;
+const MAX_SIZE: u32 = 2048;
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Format {
+    /// The picture exactly as MPD sent it
+    Original,
+    /// Rather than WebP, as the only WebP encoder available is lossless,
+    /// which is several times larger for photos
+    Jpeg,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Original => "orig",
+            Format::Jpeg => "jpg",
+        }
+    }
+}
+
+/// How a picture is sent to the browser.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rendition {
+    /// The maximum width and height, `None` being the original size
+    pub size: Option<u32>,
+    pub format: Format,
+}
+
+impl Rendition {
+    pub const ORIGINAL: Self = Self {
+        size: None,
+        format: Format::Original,
+    };
+
+    pub fn new(size: Option<u32>) -> Self {
+        let Some(size) = size else {
+            return Self::ORIGINAL;
+        };
+
+        Self {
+            size: Some(size.clamp(1, MAX_SIZE).div_ceil(SIZE_STEP) * SIZE_STEP),
+            format: Format::Jpeg,
+        }
+    }
+
+    /// The name of this rendition of the picture with the given hash, also
+    /// used as its `ETag`.
+    pub fn name(&self, hash: &str) -> String {
+        let size = self.size.map_or("full".into(), |size| size.to_string());
+        format!("{hash}-{size}.{}", self.format.extension())
+    }
+
+    /// Downscales and re-encodes the original picture.
+    pub fn render(&self, original: &[u8]) -> ImageResult<Vec<u8>> {
+        if self.format == Format::Original {
+            return Ok(original.to_vec());
+        }
+
+        let mut image = image::load_from_memory(original)?;
+        if let Some(size) = self.size {
+            if image.width() > size || image.height() > size {
+                image = image.resize(size, size, FilterType::Lanczos3);
+            }
+        }
+
+        let mut data = Cursor::new(Vec::new());
+        let image = DynamicImage::ImageRgb8(image.to_rgb8());
+        JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY).encode_image(&image)?;
+        Ok(data.into_inner())
+    }
+
+    pub fn mime_type(&self, data: &[u8]) -> &'static str {
+        match self.format {
+            Format::Original => infer::get(data)
+                .map(|kind| kind.mime_type())
+                .unwrap_or("application/octet-stream"),
+            Format::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// What a picture is looked up by, so that all songs of an album share it
+/// and browsers only fetch it once.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Album {
+    /// The songs in a directory, like MPD's `albumart` assumes
+    Directory(String),
+    /// The songs with the same AlbumArtist and Album tags, for songs in the
+    /// root of the music directory
+    Tags { artist: String, album: String },
+    /// A single song, for streams and songs lacking tags
+    Song(String),
+}
+
+impl Album {
+    /// The album of the song at `path`, as far as can be told from the path.
+    pub fn from_path(path: &str) -> Self {
+        match path.rsplit_once('/') {
+            Some((directory, _)) if !path.contains("://") => Self::Directory(directory.to_string()),
+            _ => Self::Song(path.to_string()),
+        }
+    }
+
+    /// A cache key, unique across servers.
+    pub fn key(&self, server: &str) -> String {
+        match self {
+            Album::Directory(directory) => format!("{server}/{directory}"),
+            Album::Tags { artist, album } => format!("{server}\0{artist}\0{album}"),
+            Album::Song(path) => format!("{server}\0{path}"),
+        }
+    }
+
+    /// The URL of the picture, relative to a server's pages.
+    pub fn url(&self, size: Option<u32>) -> String {
+        let encode = |value| utf8_percent_encode(value, NON_ALPHANUMERIC);
+        let mut url = match self {
+            Album::Directory(directory) => format!("art?dir={}", encode(directory)),
+            Album::Tags { artist, album } => {
+                format!("art?albumartist={}&album={}", encode(artist), encode(album))
+            }
+            Album::Song(path) => format!("art?path={}", encode(path)),
+        };
+        if let Some(size) = size {
+            url += &format!("&size={size}");
+        }
+        url
+    }
+}
+
+/// The URL of the picture of the song at `path`, for templates.
+pub fn url(path: &str, size: Option<u32>) -> String {
+    Album::from_path(path).url(size)
+}
+
+/// Identifies a picture by its contents, so albums sharing a picture share
+/// its cache entries.
+pub fn hash(data: &[u8]) -> String {
+    Sha256::digest(data)[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_sizes_up_to_steps() {
+        assert_eq!(Rendition::new(None), Rendition::ORIGINAL);
+        let size = |size| Rendition::new(Some(size)).size;
+        assert_eq!(size(0), Some(32));
+        assert_eq!(size(1), Some(32));
+        assert_eq!(size(32), Some(32));
+        assert_eq!(size(33), Some(64));
+        assert_eq!(size(2047), Some(2048));
+        assert_eq!(size(u32::MAX), Some(2048));
+        assert_eq!(Rendition::new(Some(100)).format, Format::Jpeg);
+    }
+
+    #[test]
+    fn names_renditions() {
+        assert_eq!(Rendition::ORIGINAL.name("abc"), "abc-full.orig");
+        assert_eq!(Rendition::new(Some(100)).name("abc"), "abc-128.jpg");
+    }
+
+    #[test]
+    fn renders_smaller_jpegs() {
+        let original = include_bytes!("../../static/favicon.png");
+        let data = Rendition::new(Some(16)).render(original).unwrap();
+        let image = image::load_from_memory(&data).unwrap();
+        assert_eq!(infer::get(&data).unwrap().mime_type(), "image/jpeg");
+        assert!(image.width() <= 32 && image.height() <= 32);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Shown for songs without a picture, such as most streams.
+pub const PLACEHOLDER: &[u8] = include_bytes!("../../static/placeholder.webp");
+
+/// File names looked for if `EMPEDE_ART_FILES` is not set.
+const DEFAULT_PATTERNS: &str = "cover.*,folder.*,front.*";
+
+/// Finds pictures next to songs in a local copy of MPD's music directory,
+/// which is much faster than having MPD send them.
+pub struct Resolver {
+    music_dir: Option<PathBuf>,
+    /// Lowercase file name patterns in order of preference, where `*`
+    /// matches anything
+    patterns: Vec<String>,
+}
+
+impl Resolver {
+    /// The resolver configured by `EMPEDE_MUSIC_DIR` and `EMPEDE_ART_FILES`.
+    pub fn global() -> &'static Resolver {
+        static RESOLVER: OnceLock<Resolver> = OnceLock::new();
+        RESOLVER.get_or_init(Self::from_env)
+    }
+
+    fn from_env() -> Self {
+        let music_dir = std::env::var("EMPEDE_MUSIC_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .and_then(|dir| match std::fs::canonicalize(&dir) {
+                Ok(dir) => Some(dir),
+                Err(e) => {
+                    println!("Not reading album art from {dir} ({e})");
+                    None
+                }
+            });
+
+        let patterns = std::env::var("EMPEDE_ART_FILES")
+            .unwrap_or(DEFAULT_PATTERNS.into())
+            .split(',')
+            .map(|pattern| pattern.trim().to_lowercase())
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+
+        Self {
+            music_dir,
+            patterns,
+        }
+    }
+
+    /// Returns the picture in the directory of the song at `path`, relative
+    /// to the music directory, or in that directory if `path` is one. Songs
+    /// in the music directory itself have none, as they are not an album.
+    pub async fn local(&self, path: &str) -> Option<Vec<u8>> {
+        let music_dir = self.music_dir.as_ref()?;
+        if path.contains("://") {
+            return None;
+        }
+
+        // Only descend into the music directory
+        let path = Path::new(path);
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+
+        let mut directory = music_dir.join(path);
+        if !tokio::fs::metadata(&directory)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            directory.pop();
+        }
+
+        // Neither may symbolic links lead out of it
+        let directory = tokio::fs::canonicalize(&directory).await.ok()?;
+        if directory == *music_dir || !directory.starts_with(music_dir) {
+            return None;
+        }
+
+        let mut names = Vec::new();
+        let mut entries = tokio::fs::read_dir(&directory).await.ok()?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        names.sort();
+
+        for pattern in &self.patterns {
+            for name in &names {
+                if !matches(pattern, &name.to_lowercase()) {
+                    continue;
+                }
+
+                let path = directory.join(name);
+                if !tokio::fs::canonicalize(&path)
+                    .await
+                    .is_ok_and(|path| path.starts_with(music_dir))
+                {
+                    continue;
+                }
+
+                match tokio::fs::read(&path).await {
+                    Ok(data) if infer::is_image(&data) => return Some(data),
+                    _ => continue,
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Matches a file name against a pattern where `*` matches anything.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("cover.*", "cover.jpg"));
+        assert!(matches("cover.*", "cover."));
+        assert!(!matches("cover.*", "cover"));
+        assert!(!matches("cover.*", "mycover.jpg"));
+        assert!(matches("*.jpg", "front.jpg"));
+        assert!(!matches("*.jpg", "front.png"));
+        assert!(matches("*cover*", "album cover (front).png"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(matches("folder.jpg", "folder.jpg"));
+        assert!(!matches("folder.jpg", "folder.jpg.bak"));
+    }
+
+    #[tokio::test]
+    async fn finds_art_only_inside_album_directories() {
+        let root = std::env::temp_dir().join(format!("empede-resolver-{}", std::process::id()));
+        let album = root.join("Artist/Album");
+        std::fs::create_dir_all(&album).unwrap();
+        let png = include_bytes!("../../static/favicon.png");
+        std::fs::write(root.join("cover.png"), png).unwrap();
+        std::fs::write(album.join("Cover.PNG"), png).unwrap();
+
+        let resolver = Resolver {
+            music_dir: Some(std::fs::canonicalize(&root).unwrap()),
+            patterns: vec!["cover.*".into()],
+        };
+        assert!(resolver.local("Artist/Album/01.flac").await.is_some());
+        assert!(resolver.local("Artist/Album").await.is_some());
+        assert!(resolver.local("Artist/01.flac").await.is_none());
+        assert!(resolver.local("song.flac").await.is_none());
+        assert!(resolver.local("").await.is_none());
+        assert!(resolver.local("../outside.flac").await.is_none());
+        assert!(resolver.local("/etc/passwd").await.is_none());
+        assert!(resolver.local("http://host/Artist/Album/x").await.is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
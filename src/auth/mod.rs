@@ -0,0 +1,272 @@
+//! Optional access control, configured by `EMPEDE_AUTH`: users logging in
+//! with a password from a users file, or a reverse proxy vouching for them
+//! with a `Remote-User` header.
+
+mod sessions;
+mod users;
+
+use std::{
+    io::{self, BufRead},
+    net::IpAddr,
+    sync::OnceLock,
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    cookie::{time, Cookie, SameSite},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, Method, StatusCode},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use actix_web_lab::middleware::Next;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+use sessions::{Sessions, SESSION_LIFETIME};
+use users::Users;
+
+const SESSION_COOKIE: &str = "empede_session";
+const REMOTE_USER: &str = "Remote-User";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// Can do anything
+    User,
+    /// Can browse and view the queue, but not change anything
+    Guest,
+}
+
+/// Who a request is made by, available to handlers as
+/// `web::ReqData<User>` unless authentication is disabled.
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+    /// The MPD password this user's requests are made with, instead of the
+    /// server's password
+    pub mpd_password: Option<String>,
+}
+
+impl User {
+    pub fn is_guest(&self) -> bool {
+        self.role == Role::Guest
+    }
+}
+
+enum Mode {
+    None,
+    /// Users log in with a password from the users file
+    Users,
+    /// A reverse proxy at one of these addresses authenticates users, and
+    /// passes their name in `Remote-User`. Users not in the users file get
+    /// the `user` role.
+    Proxy(Vec<IpAddr>),
+}
+
+pub struct Auth {
+    mode: Mode,
+    users: Users,
+    sessions: Sessions,
+}
+
+impl Auth {
+    /// Configured by `EMPEDE_AUTH` (`none`, `users` or `proxy`), with
+    /// `EMPEDE_USERS_FILE` and `EMPEDE_TRUSTED_PROXIES`. Exits if that
+    /// configuration is invalid, as running without the requested access
+    /// control would be worse.
+    pub fn global() -> &'static Auth {
+        static AUTH: OnceLock<Auth> = OnceLock::new();
+        AUTH.get_or_init(|| {
+            Self::from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid authentication configuration: {e}");
+                std::process::exit(1);
+            })
+        })
+    }
+
+    fn from_env() -> Result<Self, String> {
+        let mode = match std::env::var("EMPEDE_AUTH").as_deref() {
+            Err(_) | Ok("none") => Mode::None,
+            Ok("users") => Mode::Users,
+            Ok("proxy") => {
+                let proxies = std::env::var("EMPEDE_TRUSTED_PROXIES")
+                    .map_err(|_| "EMPEDE_TRUSTED_PROXIES is required in proxy mode")?;
+                let proxies = proxies
+                    .split(',')
+                    .map(|proxy| {
+                        proxy
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("invalid proxy address '{proxy}'"))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Mode::Proxy(proxies)
+            }
+            Ok(mode) => return Err(format!("unknown mode '{mode}' in EMPEDE_AUTH")),
+        };
+
+        let users = match (&mode, std::env::var("EMPEDE_USERS_FILE")) {
+            (Mode::None, _) => Users::empty(),
+            (_, Ok(path)) => Users::load(&path).map_err(|e| e.to_string())?,
+            (Mode::Users, Err(_)) => return Err("EMPEDE_USERS_FILE is required".into()),
+            (Mode::Proxy(_), Err(_)) => Users::empty(),
+        };
+
+        Ok(Self {
+            mode,
+            users,
+            sessions: Sessions::default(),
+        })
+    }
+
+    /// Whether users log in with a password.
+    pub fn has_login(&self) -> bool {
+        matches!(self.mode, Mode::Users)
+    }
+
+    fn authenticate(&self, req: &HttpRequest) -> Option<User> {
+        match &self.mode {
+            Mode::None => None,
+            Mode::Users => self.sessions.get(req.cookie(SESSION_COOKIE)?.value()),
+            Mode::Proxy(proxies) => {
+                if !proxies.contains(&req.peer_addr()?.ip()) {
+                    return None;
+                }
+
+                let name = req.headers().get(REMOTE_USER)?.to_str().ok()?;
+                (!name.is_empty()).then(|| {
+                    self.users.get(name).unwrap_or(User {
+                        name: name.to_string(),
+                        role: Role::User,
+                        mpd_password: None,
+                    })
+                })
+            }
+        }
+    }
+
+    /// Checks a user's password, returning a session cookie if it is right.
+    pub async fn login(&'static self, name: String, password: String) -> Option<Cookie<'static>> {
+        let user = tokio::task::spawn_blocking(move || self.users.verify(&name, &password))
+            .await
+            .ok()??;
+
+        let token = self.sessions.create(user);
+        Some(
+            Cookie::build(SESSION_COOKIE, token)
+                .path("/")
+                .http_only(true)
+                // Keeps other sites from making requests on a user's behalf
+                .same_site(SameSite::Lax)
+                .max_age(time::Duration::seconds(SESSION_LIFETIME.as_secs() as i64))
+                .finish(),
+        )
+    }
+
+    /// Ends the request's session, returning a cookie removing it.
+    pub fn logout(&self, req: &HttpRequest) -> Cookie<'static> {
+        if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+            self.sessions.remove(cookie.value());
+        }
+
+        let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+        cookie.make_removal();
+        cookie
+    }
+}
+
+/// Rejects requests that are not authenticated, or that guests are not
+/// allowed to make, which is anything but looking.
+pub async fn middleware<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let auth = Auth::global();
+    if let Mode::None = auth.mode {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let path = req.path();
+    let public = path == "/login" || path == "/logout" || path.starts_with("/static/");
+
+    match auth.authenticate(req.request()) {
+        Some(user) => {
+            // Selecting a partition only changes which one this browser shows
+            let looking = matches!(*req.method(), Method::GET | Method::HEAD)
+                || (path.starts_with("/s/") && path.ends_with("/partition/select"));
+            if user.is_guest() && !looking && !public {
+                let response = error(&req, StatusCode::FORBIDDEN, "guests cannot change anything");
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+            req.extensions_mut().insert(user);
+        }
+        None if public => {}
+        None => {
+            let response = unauthenticated(&req, auth);
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+fn unauthenticated(req: &ServiceRequest, auth: &Auth) -> HttpResponse {
+    if !auth.has_login() || req.path().starts_with("/api/") {
+        return error(req, StatusCode::UNAUTHORIZED, "authentication required");
+    }
+
+    let login = |next: &str| {
+        format!(
+            "/login?next={}",
+            utf8_percent_encode(next, NON_ALPHANUMERIC)
+        )
+    };
+
+    // htmx would swap the login page into the page instead of going there,
+    // and requests fragments, so return to the page they are part of
+    if req.headers().contains_key("HX-Request") {
+        let page = req
+            .headers()
+            .get("HX-Current-URL")
+            .and_then(|url| url.to_str().ok())
+            .and_then(|url| url.split_once("://"))
+            .and_then(|(_, url)| url.find('/').map(|path| &url[path..]))
+            .unwrap_or("/");
+        return HttpResponse::Unauthorized()
+            .insert_header(("HX-Redirect", login(page)))
+            .finish();
+    }
+
+    let next = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{query}", req.path()),
+    };
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, login(&next)))
+        .finish()
+}
+
+/// An error in the format of the JSON API for API requests, or as text.
+fn error(req: &ServiceRequest, status: StatusCode, message: &str) -> HttpResponse {
+    if req.path().starts_with("/api/") {
+        HttpResponse::build(status).json(serde_json::json!({
+            "error": { "status": status.as_u16(), "message": message }
+        }))
+    } else {
+        HttpResponse::build(status).body(message.to_string())
+    }
+}
+
+/// Reads a password from standard input and prints its hash for the users
+/// file, for `empede hash-password`.
+pub fn hash_password_from_stdin() -> io::Result<()> {
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    println!("{}", users::hash_password(password));
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::RngCore;
+
+use super::User;
+
+/// How long a login lasts.
+pub const SESSION_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Logged in users by the random token in their session cookie. Sessions
+/// are only kept in memory, so restarting logs everyone out.
+#[derive(Default)]
+pub struct Sessions(Mutex<HashMap<String, (User, Instant)>>);
+
+impl Sessions {
+    /// Starts a session, returning its token.
+    pub fn create(&self, user: User) -> String {
+        let mut bytes = [0; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+
+        let mut sessions = self.0.lock().unwrap();
+        sessions.retain(|_, (_, created)| created.elapsed() < SESSION_LIFETIME);
+        sessions.insert(token.clone(), (user, Instant::now()));
+        token
+    }
+
+    pub fn get(&self, token: &str) -> Option<User> {
+        let sessions = self.0.lock().unwrap();
+        let (user, created) = sessions.get(token)?;
+        (created.elapsed() < SESSION_LIFETIME).then(|| user.clone())
+    }
+
+    pub fn remove(&self, token: &str) {
+        self.0.lock().unwrap().remove(token);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, io, sync::OnceLock};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+use super::{Role, User};
+
+struct Entry {
+    role: Role,
+    /// An Argon2 hash in PHC string format, empty if the user can only be
+    /// authenticated by a proxy
+    hash: String,
+    mpd_password: Option<String>,
+}
+
+/// The users file, with one `name:role:hash[:mpd_password]` line per user,
+/// where the role is `user` or `guest`, and the optional MPD password gives
+/// the user that password's permissions on MPD. Empty lines and lines
+/// starting with `#` are ignored.
+pub struct Users(HashMap<String, Entry>);
+
+impl Users {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut users = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{path}:{}: {message}", number + 1),
+                )
+            };
+
+            // Hashes never contain `:`, but MPD passwords may
+            let mut fields = line.splitn(4, ':');
+            let (Some(name), Some(role), hash, mpd_password) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid("expected name:role:hash[:mpd_password]"));
+            };
+            let role = match role {
+                "user" => Role::User,
+                "guest" => Role::Guest,
+                _ => return Err(invalid("role must be user or guest")),
+            };
+            let hash = hash.unwrap_or_default().to_string();
+            if !hash.is_empty() && PasswordHash::new(&hash).is_err() {
+                return Err(invalid("invalid password hash"));
+            }
+
+            let mpd_password = mpd_password
+                .filter(|password| !password.is_empty())
+                .map(str::to_string);
+
+            users.insert(
+                name.to_string(),
+                Entry {
+                    role,
+                    hash,
+                    mpd_password,
+                },
+            );
+        }
+
+        Ok(Self(users))
+    }
+
+    pub fn empty() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn get(&self, name: &str) -> Option<User> {
+        self.0.get(name).map(|entry| User {
+            name: name.to_string(),
+            role: entry.role,
+            mpd_password: entry.mpd_password.clone(),
+        })
+    }
+
+    /// Checks a password, which is slow by design, so better done on a
+    /// blocking thread. Users without a hash are checked against a random
+    /// one, so that how long this takes does not tell which users exist.
+    pub fn verify(&self, name: &str, password: &str) -> Option<User> {
+        static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+        let entry = self.0.get(name).filter(|entry| !entry.hash.is_empty());
+        let hash = match entry {
+            Some(entry) => &entry.hash,
+            None => DUMMY_HASH.get_or_init(|| {
+                hash_password(SaltString::generate(&mut rand::rngs::OsRng).as_str())
+            }),
+        };
+        let hash = PasswordHash::new(hash).ok()?;
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        if verified && entry.is_some() {
+            self.get(name)
+        } else {
+            None
+        }
+    }
+}
+
+/// Hashes a password for the users file.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("default Argon2 parameters are valid")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads a users file with the given contents.
+    fn load(name: &str, contents: &str) -> io::Result<Users> {
+        let path = std::env::temp_dir().join(format!("empede-users-{name}-{}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let users = Users::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        users
+    }
+
+    #[test]
+    fn loads_users() {
+        let hash = hash_password("secret");
+        let users = load(
+            "valid",
+            &format!(
+                "# comment\n\nalice:user:{hash}\n  bob:guest:{hash}:  \ncarol:user::a:b\ndave:guest:{hash}:\n"
+            ),
+        )
+        .unwrap();
+
+        let alice = users.verify("alice", "secret").unwrap();
+        assert_eq!(alice.role, Role::User);
+        assert_eq!(alice.mpd_password, None);
+        assert!(users.verify("alice", "wrong").is_none());
+        assert!(users.verify("nobody", "secret").is_none());
+
+        assert!(users.get("bob").unwrap().is_guest());
+
+        // Proxy-only users have no hash to log in with
+        let carol = users.get("carol").unwrap();
+        assert_eq!(carol.mpd_password.as_deref(), Some("a:b"));
+        assert!(users.verify("carol", "").is_none());
+
+        assert_eq!(users.get("dave").unwrap().mpd_password, None);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for (name, contents) in [
+            ("fields", "# users\nalice\n"),
+            ("role", "# users\nalice:admin:\n"),
+            ("hash", "# users\nalice:user:plaintext\n"),
+        ] {
+            let error = load(name, contents).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains(":2: "), "{error}");
+        }
+    }
+}
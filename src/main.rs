@@ -1,41 +1,105 @@
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::{
+    middleware::{ErrorHandlers, Logger},
+    web, App, HttpServer,
+};
+use actix_web_lab::middleware::from_fn;
 
+mod art;
+mod auth;
 mod crate_version;
 mod mpd;
 mod routes;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("hash-password") {
+        return auth::hash_password_from_stdin();
+    }
+
     let bind = std::env::var("EMPEDE_BIND").unwrap_or("0.0.0.0:8080".into());
-    let (host, port) = bind.split_once(':').unwrap();
 
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    // Fail early on an invalid configuration instead of on the first request
+    auth::Auth::global();
+
     HttpServer::new(|| {
-        App::new().wrap(Logger::default()).service(
-            web::scope("")
-                .service(routes::index::get_index)
-                .service(routes::player::get_player)
-                .service(routes::browser::get_browser)
-                .service(routes::art::get_art)
-                .service(routes::sse::idle)
-                .service(routes::queue::get_queue)
-                .service(routes::queue::post_queue)
-                .service(routes::queue::delete_queue)
-                .service(routes::queue::post_queue_move)
-                .service(routes::controls::post_play)
-                .service(routes::controls::post_pause)
-                .service(routes::controls::post_previous)
-                .service(routes::controls::post_next)
-                .service(routes::controls::post_consume)
-                .service(routes::controls::post_random)
-                .service(routes::controls::post_repeat)
-                .service(routes::controls::post_single)
-                .service(routes::controls::post_shuffle)
-                .service(actix_files::Files::new("/static", "./static")),
-        )
+        App::new()
+            .wrap(from_fn(auth::middleware))
+            .wrap(Logger::default())
+            .service(routes::index::get_root)
+            .service(routes::login::get_login)
+            .service(routes::login::post_login)
+            .service(routes::login::post_logout)
+            .service(routes::api::get_openapi)
+            .service(
+                web::scope("/api/v1")
+                    .wrap(ErrorHandlers::new().default_handler(routes::api::json_error))
+                    .service(routes::api::get_servers)
+                    .service(web::scope("/servers/{server}").configure(routes::api::configure))
+                    .configure(routes::api::configure)
+                    .default_service(web::to(routes::api::not_found)),
+            )
+            .service(
+                web::scope("/s/{server}")
+                    .service(routes::index::get_index)
+                    .service(routes::player::get_player)
+                    .service(routes::browser::get_browser)
+                    .service(routes::search::get_search)
+                    .service(routes::search::post_search_queue)
+                    .service(routes::library::get_artists)
+                    .service(routes::library::get_artist)
+                    .service(routes::library::get_album)
+                    .service(routes::library::get_genres)
+                    .service(routes::library::get_genre)
+                    .service(routes::library::post_library_queue)
+                    .service(routes::playlists::get_playlists)
+                    .service(routes::playlists::post_playlists)
+                    .service(routes::playlists::get_playlist)
+                    .service(routes::playlists::delete_playlist)
+                    .service(routes::playlists::post_playlist_rename)
+                    .service(routes::playlists::post_playlist_add)
+                    .service(routes::playlists::delete_playlist_song)
+                    .service(routes::playlists::post_playlist_move)
+                    .service(routes::playlists::post_playlist_load)
+                    .service(routes::art::get_art)
+                    .service(routes::sse::idle)
+                    .service(routes::queue::get_queue)
+                    .service(routes::queue::post_queue)
+                    .service(routes::queue::delete_queue)
+                    .service(routes::queue::post_queue_move)
+                    .service(routes::queue::post_queue_send)
+                    .service(routes::controls::post_play)
+                    .service(routes::controls::post_pause)
+                    .service(routes::controls::post_previous)
+                    .service(routes::controls::post_next)
+                    .service(routes::controls::post_seek)
+                    .service(routes::controls::post_consume)
+                    .service(routes::controls::post_random)
+                    .service(routes::controls::post_repeat)
+                    .service(routes::controls::post_single)
+                    .service(routes::controls::post_shuffle)
+                    .service(routes::volume::get_volume)
+                    .service(routes::volume::post_volume)
+                    .service(routes::volume::post_volume_mute)
+                    .service(routes::outputs::get_outputs)
+                    .service(routes::outputs::post_output_enable)
+                    .service(routes::outputs::post_output_disable)
+                    .service(routes::outputs::post_output_toggle)
+                    .service(routes::outputs::post_output_attribute)
+                    .service(routes::outputs::post_output_move)
+                    .service(routes::partitions::get_partitions)
+                    .service(routes::partitions::post_partitions)
+                    .service(routes::partitions::delete_partition)
+                    .service(routes::partitions::post_partition_select),
+            )
+            .service(actix_files::Files::new("/static", "./static"))
+    })
+    .bind(&bind)
+    .unwrap_or_else(|e| {
+        eprintln!("Could not bind to EMPEDE_BIND={bind} ({e})");
+        std::process::exit(1);
     })
-    .bind((host, port.parse().unwrap()))?
     .run()
     .await?;
 
@@ -0,0 +1,227 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use super::{CommandResult, Error};
+
+/// The protocol version MPD announces in its `OK MPD x.y.z` greeting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::Protocol(format!("invalid protocol version '{s}'"));
+
+        let mut parts = s.trim().splitn(3, '.');
+        let mut next = || -> Result<u32, Error> {
+            match parts.next() {
+                Some(part) => part.parse().map_err(|_| invalid()),
+                None => Ok(0),
+            }
+        };
+
+        Ok(Self(next()?, next()?, next()?))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Protocol features that not every MPD version supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    AlbumArt,
+    ReadPicture,
+    BinaryLimit,
+    AddPosition,
+    SearchAdd,
+    FindAdd,
+    PlaylistLength,
+    GetVol,
+    OutputSet,
+    Partitions,
+    PlchangesRange,
+}
+
+impl Feature {
+    fn min_version(self) -> Version {
+        match self {
+            Self::AlbumArt => Version(0, 21, 0),
+            Self::ReadPicture => Version(0, 22, 0),
+            Self::BinaryLimit => Version(0, 22, 4),
+            Self::AddPosition => Version(0, 23, 1),
+            // Filter expressions were introduced in 0.21
+            Self::SearchAdd | Self::FindAdd => Version(0, 21, 0),
+            Self::PlaylistLength => Version(0, 24, 0),
+            Self::GetVol => Version(0, 23, 0),
+            Self::OutputSet => Version(0, 21, 0),
+            Self::Partitions => Version(0, 22, 0),
+            Self::PlchangesRange => Version(0, 21, 0),
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            Self::AlbumArt => "albumart",
+            Self::ReadPicture => "readpicture",
+            Self::BinaryLimit => "binarylimit",
+            Self::AddPosition => "add",
+            Self::SearchAdd => "searchadd",
+            Self::FindAdd => "findadd",
+            Self::PlaylistLength => "playlistlength",
+            Self::GetVol => "getvol",
+            Self::OutputSet => "outputset",
+            Self::Partitions => "partition",
+            Self::PlchangesRange => "plchanges",
+        }
+    }
+}
+
+/// MPD's permissions beyond `read`, which every connection is assumed to
+/// have. The UI hides what a connection may not do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    /// Adding songs to the queue and loading playlists
+    pub add: bool,
+    /// Playback, volume, changing the queue and editing stored playlists
+    pub control: bool,
+    /// Outputs and partitions
+    pub admin: bool,
+}
+
+impl Permissions {
+    pub const ALL: Self = Self {
+        add: true,
+        control: true,
+        admin: true,
+    };
+
+    pub const READ: Self = Self {
+        add: false,
+        control: false,
+        admin: false,
+    };
+}
+
+/// What the server we are connected to can do, derived from its protocol
+/// version and the output of `commands` and `tagtypes`. Note that `commands`
+/// only lists the commands the connection has permission to use.
+#[derive(Debug, Default, Clone)]
+pub struct Capabilities {
+    pub version: Version,
+    commands: HashSet<String>,
+    tag_types: HashSet<String>,
+}
+
+impl Capabilities {
+    pub fn new(version: Version, commands: CommandResult, tag_types: CommandResult) -> Self {
+        fn values(result: CommandResult, key: &str) -> HashSet<String> {
+            result
+                .properties
+                .into_iter()
+                .filter(|(k, _)| k == key)
+                .map(|(_, value)| value)
+                .collect()
+        }
+
+        Self {
+            version,
+            commands: values(commands, "command"),
+            tag_types: values(tag_types, "tagtype"),
+        }
+    }
+
+    pub fn has_command(&self, command: &str) -> bool {
+        self.commands.contains(command)
+    }
+
+    pub fn has_tag_type(&self, tag_type: &str) -> bool {
+        self.tag_types.contains(tag_type)
+    }
+
+    /// The tag to group albums by: `AlbumArtist`, unless MPD was configured
+    /// without it.
+    pub fn album_artist_tag(&self) -> &'static str {
+        if self.has_tag_type("AlbumArtist") {
+            "AlbumArtist"
+        } else {
+            "Artist"
+        }
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.version >= feature.min_version() && self.has_command(feature.command())
+    }
+
+    /// What the connection's password allows, told from a command that
+    /// needs each permission.
+    pub fn permissions(&self) -> Permissions {
+        Permissions {
+            add: self.has_command("add"),
+            control: self.has_command("play"),
+            admin: self.has_command("enableoutput"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!("0.23.5".parse::<Version>().unwrap(), Version(0, 23, 5));
+        assert_eq!("0.24.0\n".parse::<Version>().unwrap(), Version(0, 24, 0));
+        assert_eq!("0.21".parse::<Version>().unwrap(), Version(0, 21, 0));
+        assert!("".parse::<Version>().is_err());
+        assert!("0.x.1".parse::<Version>().is_err());
+        assert!("0.23.5.1".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn orders_versions() {
+        assert!(Version(0, 22, 4) > Version(0, 22, 0));
+        assert!(Version(0, 23, 0) > Version(0, 22, 10));
+        assert_eq!(Version(0, 21, 0).to_string(), "0.21.0");
+    }
+
+    fn capabilities(version: Version, commands: &[&str]) -> Capabilities {
+        let commands = commands
+            .iter()
+            .map(|command| ("command".to_string(), command.to_string()))
+            .collect();
+        Capabilities::new(
+            version,
+            CommandResult::new(commands),
+            CommandResult::new(Vec::new()),
+        )
+    }
+
+    #[test]
+    fn needs_version_and_command_for_features() {
+        let old = capabilities(Version(0, 20, 0), &["albumart"]);
+        assert!(!old.supports(Feature::AlbumArt));
+
+        let denied = capabilities(Version(0, 23, 0), &["status"]);
+        assert!(!denied.supports(Feature::AlbumArt));
+
+        let new = capabilities(Version(0, 23, 0), &["albumart"]);
+        assert!(new.supports(Feature::AlbumArt));
+        assert!(!new.supports(Feature::ReadPicture));
+    }
+
+    #[test]
+    fn tells_permissions_from_commands() {
+        let read = capabilities(Version(0, 23, 0), &["status", "playlistinfo"]);
+        assert_eq!(read.permissions(), Permissions::READ);
+
+        let add = capabilities(Version(0, 23, 0), &["status", "add"]);
+        assert!(add.permissions().add && !add.permissions().control);
+
+        let all = capabilities(Version(0, 23, 0), &["add", "play", "enableoutput"]);
+        assert_eq!(all.permissions(), Permissions::ALL);
+    }
+}
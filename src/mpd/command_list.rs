@@ -0,0 +1,105 @@
+use super::{play_command, CommandResult, Error, Feature, Filter, Mpd, Result};
+
+/// A batch of commands that is sent to MPD in one go, see
+/// [`Mpd::command_list`].
+pub struct CommandList<'a> {
+    mpd: &'a mut Mpd,
+    commands: Vec<String>,
+}
+
+impl<'a> CommandList<'a> {
+    pub(super) fn new(mpd: &'a mut Mpd) -> Self {
+        Self {
+            mpd,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn command(&mut self, command: impl Into<String>) -> &mut Self {
+        self.commands.push(command.into());
+        self
+    }
+
+    pub fn clear(&mut self) -> &mut Self {
+        self.command("clear")
+    }
+
+    pub fn add(&mut self, path: &str) -> &mut Self {
+        let path = Mpd::escape_str(path);
+        self.command(format!(r#"add "{path}""#))
+    }
+
+    pub fn add_position(&mut self, path: &str, position: &str) -> Result<&mut Self> {
+        if !self.mpd.capabilities.supports(Feature::AddPosition) {
+            return Err(Error::Unsupported(Feature::AddPosition));
+        }
+
+        let path = Mpd::escape_str(path);
+        let position = Mpd::escape_str(position);
+        Ok(self.command(format!(r#"add "{path}" "{position}""#)))
+    }
+
+    /// Adds all songs exactly matching `filter`, optionally at `position`.
+    pub fn find_add(&mut self, filter: &Filter, position: Option<&str>) -> Result<&mut Self> {
+        if !self.mpd.capabilities.supports(Feature::FindAdd) {
+            return Err(Error::Unsupported(Feature::FindAdd));
+        }
+
+        let filter = Mpd::escape_str(&filter.to_string());
+        let command = match position {
+            Some(position) => {
+                if !self.mpd.capabilities.supports(Feature::AddPosition) {
+                    return Err(Error::Unsupported(Feature::AddPosition));
+                }
+                let position = Mpd::escape_str(position);
+                format!(r#"findadd "{filter}" position "{position}""#)
+            }
+            None => format!(r#"findadd "{filter}""#),
+        };
+        Ok(self.command(command))
+    }
+
+    /// Loads a stored playlist into the queue, optionally at `position`.
+    pub fn load(&mut self, name: &str, position: Option<&str>) -> Result<&mut Self> {
+        let name = Mpd::escape_str(name);
+        let command = match position {
+            Some(position) => {
+                if !self.mpd.capabilities.supports(Feature::AddPosition) {
+                    return Err(Error::Unsupported(Feature::AddPosition));
+                }
+                let position = Mpd::escape_str(position);
+                format!(r#"load "{name}" 0: "{position}""#)
+            }
+            None => format!(r#"load "{name}""#),
+        };
+        Ok(self.command(command))
+    }
+
+    pub fn play(&mut self, position: Option<&str>) -> &mut Self {
+        self.command(play_command(position))
+    }
+
+    /// Sends the commands using `command_list_ok_begin`, returning one
+    /// [`CommandResult`] per command.
+    pub async fn run(self) -> Result<Vec<CommandResult>> {
+        if self.commands.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let request = format!(
+            "command_list_ok_begin\n{}\ncommand_list_end",
+            self.commands.join("\n")
+        );
+        let results = self.mpd.request(&request, true).await?;
+
+        if results.len() != self.commands.len() {
+            return Err(Error::Protocol(format!(
+                "expected {} results from command list, got {}",
+                self.commands.len(),
+                results.len()
+            )));
+        }
+
+        Ok(results)
+    }
+}
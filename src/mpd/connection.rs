@@ -0,0 +1,217 @@
+use std::{
+    io,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// Where to find the MPD server, following the same `MPD_HOST` conventions as
+/// `mpc` and libmpdclient:
+///
+/// - `hostname` or `192.168.1.2` connects over TCP to `MPD_PORT`
+/// - `/run/mpd/socket` connects to a Unix domain socket
+/// - `@mpd` connects to the Linux abstract socket named `mpd`
+/// - any of the above prefixed with `password@` also sets the password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    Tcp { host: String, port: u16 },
+    Unix(PathBuf),
+    Abstract(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub address: Address,
+    pub password: Option<String>,
+}
+
+impl ConnectionInfo {
+    pub fn parse(host: &str, port: u16) -> Self {
+        let (password, host) = match host.split_once('@') {
+            Some((password, host)) if !password.is_empty() => (Some(password.to_string()), host),
+            _ => (None, host),
+        };
+
+        let address = if let Some(name) = host.strip_prefix('@') {
+            Address::Abstract(name.to_string())
+        } else if host.starts_with('/') {
+            Address::Unix(host.into())
+        } else {
+            // Brackets only separate IPv6 addresses from the port
+            let host = host
+                .strip_prefix('[')
+                .and_then(|host| host.strip_suffix(']'))
+                .unwrap_or(host);
+            Address::Tcp {
+                host: host.to_string(),
+                port,
+            }
+        };
+
+        Self { address, password }
+    }
+
+    /// `MPD_PORT`, or MPD's default port.
+    pub fn default_port() -> u16 {
+        std::env::var("MPD_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(6600)
+    }
+
+    pub fn from_env() -> Self {
+        let host = std::env::var("MPD_HOST").unwrap_or("localhost".to_string());
+
+        let mut info = Self::parse(&host, Self::default_port());
+        if info.password.is_none() {
+            info.password = std::env::var("MPD_PASSWORD")
+                .ok()
+                .filter(|password| !password.is_empty());
+        }
+        info
+    }
+}
+
+/// A connection to MPD over any of the supported transports.
+#[derive(Debug)]
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    pub async fn connect(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp { host, port } => {
+                Ok(Self::Tcp(TcpStream::connect((host.as_str(), *port)).await?))
+            }
+
+            #[cfg(unix)]
+            Address::Unix(path) => Ok(Self::Unix(UnixStream::connect(path).await?)),
+
+            #[cfg(target_os = "linux")]
+            Address::Abstract(name) => {
+                use std::os::linux::net::SocketAddrExt;
+
+                // Connecting to a local socket does not block, so the std
+                // implementation is fine to use here
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                let stream = std::os::unix::net::UnixStream::connect_addr(&addr)?;
+                stream.set_nonblocking(true)?;
+                Ok(Self::Unix(UnixStream::from_std(stream)?))
+            }
+
+            #[allow(unreachable_patterns)]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{address:?} is not supported on this platform"),
+            )),
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(host: &str) -> (Address, Option<String>) {
+        let info = ConnectionInfo::parse(host, 6600);
+        (info.address, info.password)
+    }
+
+    fn tcp(host: &str) -> Address {
+        Address::Tcp {
+            host: host.into(),
+            port: 6600,
+        }
+    }
+
+    #[test]
+    fn parses_tcp_hosts() {
+        assert_eq!(parse("localhost"), (tcp("localhost"), None));
+        assert_eq!(parse("192.168.1.2"), (tcp("192.168.1.2"), None));
+        assert_eq!(parse("::1"), (tcp("::1"), None));
+        assert_eq!(parse("[::1]"), (tcp("::1"), None));
+    }
+
+    #[test]
+    fn parses_sockets() {
+        assert_eq!(
+            parse("/run/mpd/socket"),
+            (Address::Unix("/run/mpd/socket".into()), None)
+        );
+        assert_eq!(parse("@mpd"), (Address::Abstract("mpd".into()), None));
+    }
+
+    #[test]
+    fn parses_passwords() {
+        assert_eq!(
+            parse("secret@localhost"),
+            (tcp("localhost"), Some("secret".into()))
+        );
+        assert_eq!(
+            parse("secret@/run/mpd/socket"),
+            (
+                Address::Unix("/run/mpd/socket".into()),
+                Some("secret".into())
+            )
+        );
+        assert_eq!(
+            parse("secret@@mpd"),
+            (Address::Abstract("mpd".into()), Some("secret".into()))
+        );
+    }
+}
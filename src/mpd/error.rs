@@ -0,0 +1,204 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+
+use super::Feature;
+
+/// Error codes MPD sends in `ACK` responses, see `src/protocol/Ack.hxx` in
+/// the MPD source tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckCode {
+    NotList,
+    Arg,
+    Password,
+    Permission,
+    Unknown,
+    NoExist,
+    PlaylistMax,
+    System,
+    PlaylistLoad,
+    UpdateAlready,
+    PlayerSync,
+    Exist,
+    Other(u32),
+}
+
+impl From<u32> for AckCode {
+    fn from(code: u32) -> Self {
+        match code {
+            1 => Self::NotList,
+            2 => Self::Arg,
+            3 => Self::Password,
+            4 => Self::Permission,
+            5 => Self::Unknown,
+            50 => Self::NoExist,
+            51 => Self::PlaylistMax,
+            52 => Self::System,
+            53 => Self::PlaylistLoad,
+            54 => Self::UpdateAlready,
+            55 => Self::PlayerSync,
+            56 => Self::Exist,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<AckCode> for u32 {
+    fn from(code: AckCode) -> Self {
+        match code {
+            AckCode::NotList => 1,
+            AckCode::Arg => 2,
+            AckCode::Password => 3,
+            AckCode::Permission => 4,
+            AckCode::Unknown => 5,
+            AckCode::NoExist => 50,
+            AckCode::PlaylistMax => 51,
+            AckCode::System => 52,
+            AckCode::PlaylistLoad => 53,
+            AckCode::UpdateAlready => 54,
+            AckCode::PlayerSync => 55,
+            AckCode::Exist => 56,
+            AckCode::Other(code) => code,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("MPD error {code:?} in command {list_num} ({command}): {message}")]
+    Ack {
+        code: AckCode,
+        list_num: u32,
+        command: String,
+        message: String,
+    },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("MPD protocol violation: {0}")]
+    Protocol(String),
+
+    #[error("connection to MPD lost")]
+    Disconnected,
+
+    #[error("{0:?} is not supported by this MPD server")]
+    Unsupported(Feature),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Parses an `ACK [error@command_listNum] {current_command} message_text`
+    /// line into an [`Error::Ack`]. Anything else is a protocol violation.
+    pub fn from_ack(line: &str) -> Self {
+        fn parse(line: &str) -> Option<Error> {
+            let rest = line.trim_end().strip_prefix("ACK [")?;
+            let (code, rest) = rest.split_once('@')?;
+            let (list_num, rest) = rest.split_once("] {")?;
+            let (command, message) = rest.split_once('}')?;
+
+            Some(Error::Ack {
+                code: code.parse::<u32>().ok()?.into(),
+                list_num: list_num.parse().ok()?,
+                command: command.to_string(),
+                message: message.trim_start().to_string(),
+            })
+        }
+
+        parse(line)
+            .unwrap_or_else(|| Error::Protocol(format!("malformed ACK '{}'", line.trim_end())))
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Ack { code, .. } => match code {
+                AckCode::NoExist => StatusCode::NOT_FOUND,
+                AckCode::Password | AckCode::Permission => StatusCode::FORBIDDEN,
+                AckCode::Arg | AckCode::Unknown | AckCode::NotList => StatusCode::BAD_REQUEST,
+                AckCode::Exist | AckCode::UpdateAlready => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::Io(_) | Self::Protocol(_) | Self::Disconnected => StatusCode::BAD_GATEWAY,
+            Self::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_acks() {
+        let Error::Ack {
+            code,
+            list_num,
+            command,
+            message,
+        } = Error::from_ack("ACK [50@3] {playlistinfo} No such song\n")
+        else {
+            panic!("not an ACK");
+        };
+        assert_eq!(code, AckCode::NoExist);
+        assert_eq!(list_num, 3);
+        assert_eq!(command, "playlistinfo");
+        assert_eq!(message, "No such song");
+    }
+
+    #[test]
+    fn parses_acks_without_a_command_or_message() {
+        let Error::Ack {
+            code,
+            command,
+            message,
+            ..
+        } = Error::from_ack("ACK [5@0] {} ")
+        else {
+            panic!("not an ACK");
+        };
+        assert_eq!(code, AckCode::Unknown);
+        assert_eq!(command, "");
+        assert_eq!(message, "");
+    }
+
+    #[test]
+    fn keeps_unknown_codes() {
+        let Error::Ack { code, .. } = Error::from_ack("ACK [99@0] {x} y") else {
+            panic!("not an ACK");
+        };
+        assert_eq!(code, AckCode::Other(99));
+        assert_eq!(u32::from(code), 99);
+    }
+
+    #[test]
+    fn rejects_malformed_acks() {
+        for line in [
+            "",
+            "OK",
+            "ACK",
+            "ACK [2@0 {x} y",
+            "ACK [x@0] {x} y",
+            "ACK [2@-1] {x} y",
+            "ACK [2@0] x y",
+        ] {
+            assert!(
+                matches!(Error::from_ack(line), Error::Protocol(_)),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn maps_codes_to_statuses() {
+        let status = |line| Error::from_ack(line).status_code();
+        assert_eq!(status("ACK [50@0] {x} y"), StatusCode::NOT_FOUND);
+        assert_eq!(status("ACK [4@0] {x} y"), StatusCode::FORBIDDEN);
+        assert_eq!(status("ACK [2@0] {x} y"), StatusCode::BAD_REQUEST);
+        assert_eq!(status("ACK [56@0] {x} y"), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+/// An MPD filter expression (MPD 0.21+), e.g. `((Artist == 'Foo') AND (Album == 'Bar'))`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter(String);
+
+impl Filter {
+    fn compare(tag: &str, operator: &str, value: &str) -> Self {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('\'', "\\'")
+            .replace('"', "\\\"");
+        Self(format!("({tag} {operator} '{value}')"))
+    }
+
+    pub fn equals(tag: &str, value: &str) -> Self {
+        Self::compare(tag, "==", value)
+    }
+
+    pub fn contains(tag: &str, value: &str) -> Self {
+        Self::compare(tag, "contains", value)
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        Self(format!("({} AND {})", self.0, other.0))
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_comparisons() {
+        let filter = Filter::equals("Artist", "Foo").and(Filter::contains("Album", "Bar"));
+        assert_eq!(
+            filter.to_string(),
+            "((Artist == 'Foo') AND (Album contains 'Bar'))"
+        );
+    }
+
+    #[test]
+    fn escapes_values() {
+        assert_eq!(
+            Filter::equals("Title", r#"It's a "test" \o/"#).to_string(),
+            r#"(Title == 'It\'s a \"test\" \\o/')"#
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use std::{ops::Range, time::Duration};
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use utoipa::ToSchema;
+
+mod capabilities;
+mod command_list;
+mod connection;
+mod error;
+mod filter;
+mod models;
+mod outputs;
+mod partitions;
+mod playlists;
+mod pool;
+mod servers;
+pub use capabilities::{Capabilities, Feature, Permissions};
+pub use command_list::CommandList;
+pub use connection::{ConnectionInfo, Stream};
+pub use error::{AckCode, Error, Result};
+pub use filter::Filter;
+pub use models::{PlayerState, Song, Stats, Status};
+pub use outputs::Output;
+pub use partitions::DEFAULT_PARTITION;
+pub use playlists::StoredPlaylist;
+pub use pool::{Pool, PooledMpd};
+pub use servers::Server;
+
+/// Commands that are safe to send a second time after the connection dropped
+/// halfway through, because repeating them has the same effect as sending
+/// them once.
+const IDEMPOTENT_COMMANDS: &[&str] = &[
+    "albumart",
+    "binarylimit",
+    "command_list_end",
+    "command_list_ok_begin",
+    "consume",
+    "currentsong",
+    "disableoutput",
+    "enableoutput",
+    "getvol",
+    "listplaylistinfo",
+    "listplaylists",
+    "lsinfo",
+    "outputs",
+    "ping",
+    "playlistinfo",
+    "playlistlength",
+    "plchanges",
+    "random",
+    "readpicture",
+    "repeat",
+    "setvol",
+    "single",
+    "stats",
+    "status",
+];
+
+const RECONNECT_ATTEMPTS: u32 = 6;
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+
+fn play_command(position: Option<&str>) -> String {
+    match position {
+        Some(position) => {
+            let position = Mpd::escape_str(position);
+            format!(r#"play "{position}""#)
+        }
+        None => "play".into(),
+    }
+}
+
+/// The queue, or part of it, as of a queue version.
+pub struct Queue {
+    /// The queue version from `status`, which changes with every modification
+    pub version: u32,
+    pub length: u32,
+    /// The position of the current song
+    pub current: Option<u32>,
+    pub items: Vec<QueueItem>,
+}
+
+pub struct QueueItem {
+    pub id: u32,
+    pub position: u32,
+    pub file: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub playing: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Entry {
+    Song {
+        track: Option<u32>,
+        name: String,
+        artist: String,
+        path: String,
+    },
+    Directory {
+        name: String,
+        path: String,
+    },
+    Playlist {
+        name: String,
+        path: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Mpd {
+    info: ConnectionInfo,
+    bufstream: Option<BufStream<Stream>>,
+    capabilities: Capabilities,
+    /// The partition this connection is bound to, `None` being the default one
+    partition: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CommandResult {
+    properties: Vec<(String, String)>,
+    binary: Option<Vec<u8>>,
+}
+
+impl CommandResult {
+    pub fn new(properties: Vec<(String, String)>) -> Self {
+        Self {
+            properties,
+            binary: None,
+        }
+    }
+
+    pub fn new_binary(properties: Vec<(String, String)>, binary: Vec<u8>) -> Self {
+        Self {
+            properties,
+            binary: Some(binary),
+        }
+    }
+
+    /// Splits the properties into groups, each starting at one of the keys
+    /// in `split_at`. Properties before the first such key are dropped.
+    pub fn into_groups(self, split_at: &[&str]) -> Vec<Vec<(String, String)>> {
+        let mut output = Vec::new();
+        let mut current: Option<Vec<_>> = None;
+
+        for (key, value) in self.properties {
+            if split_at.contains(&key.as_str()) {
+                if let Some(current) = current {
+                    output.push(current);
+                }
+                current = Some(Vec::new());
+            }
+
+            if let Some(current) = current.as_mut() {
+                current.push((key, value));
+            }
+        }
+
+        if let Some(current) = current {
+            output.push(current);
+        }
+
+        output
+    }
+
+    pub fn into_status(self) -> Result<Status> {
+        Status::from_properties(self.properties)
+    }
+
+    pub fn into_song(self) -> Result<Option<Song>> {
+        if self.properties.is_empty() {
+            return Ok(None);
+        }
+        Song::from_properties(self.properties).map(Some)
+    }
+
+    pub fn into_songs(self) -> Result<Vec<Song>> {
+        self.into_groups(&["file"])
+            .into_iter()
+            .map(Song::from_properties)
+            .collect()
+    }
+
+    pub fn into_stats(self) -> Result<Stats> {
+        Stats::from_properties(self.properties)
+    }
+}
+
+impl Mpd {
+    pub fn escape_str(s: &str) -> String {
+        s.replace('\\', "\\\\")
+            .replace('\"', "\\\"")
+            .replace('\'', "\\'")
+    }
+
+    pub fn new(info: ConnectionInfo) -> Self {
+        Self {
+            info,
+            bufstream: None,
+            capabilities: Capabilities::default(),
+            partition: None,
+        }
+    }
+
+    pub async fn connect(&mut self) -> Result<()> {
+        self.bufstream = None;
+
+        let stream = Stream::connect(&self.info.address).await?;
+        self.bufstream = Some(BufStream::new(stream));
+
+        // A connection that is not fully set up, e.g. because the password
+        // was refused, must not be used or returned to a pool
+        let result = self.handshake().await;
+        if result.is_err() {
+            self.bufstream = None;
+        }
+        result
+    }
+
+    async fn handshake(&mut self) -> Result<()> {
+        let mut buffer = String::new();
+        self.read_line(&mut buffer).await?;
+        let version = match buffer.strip_prefix("OK MPD ") {
+            Some(version) => version.parse()?,
+            None => {
+                return Err(Error::Protocol(format!(
+                    "unexpected greeting '{}'",
+                    buffer.trim_end()
+                )));
+            }
+        };
+
+        if let Some(password) = &self.info.password {
+            let password = Self::escape_str(password);
+            self.send_command(&format!(r#"password "{password}""#))
+                .await?;
+        }
+
+        let commands = self.send_command("commands").await?;
+        let tag_types = self.send_command("tagtypes").await?;
+        self.capabilities = Capabilities::new(version, commands, tag_types);
+
+        if self.capabilities.supports(Feature::BinaryLimit) {
+            self.send_command("binarylimit 1048576").await?;
+        }
+
+        if let Some(partition) = &self.partition {
+            let partition = Self::escape_str(partition);
+            self.send_command(&format!(r#"partition "{partition}""#))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.bufstream.is_some()
+    }
+
+    /// Drops the current connection and connects again, backing off
+    /// exponentially while MPD is unreachable (e.g. while it restarts). This
+    /// is meant for long-lived connections like `idle` listeners, requests
+    /// only try connecting once so they fail fast.
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.bufstream = None;
+
+        let mut delay = RECONNECT_INITIAL_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.connect().await {
+                Ok(()) => return Ok(()),
+                // MPD is up but refused us (e.g. a wrong password), retrying won't help
+                Err(e @ Error::Ack { .. }) => return Err(e),
+                Err(e) if attempt < RECONNECT_ATTEMPTS => {
+                    println!("Could not connect to MPD ({e}), retrying in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn is_idempotent(command: &str) -> bool {
+        let mut words = command.split_whitespace();
+        match words.next().unwrap_or_default() {
+            // Without an argument, `pause` toggles
+            "pause" => matches!(words.next(), Some("0" | "1")),
+            name => IDEMPOTENT_COMMANDS.contains(&name),
+        }
+    }
+
+    fn stream(&mut self) -> Result<&mut BufStream<Stream>> {
+        self.bufstream.as_mut().ok_or(Error::Disconnected)
+    }
+
+    async fn read_line(&mut self, buffer: &mut String) -> Result<()> {
+        buffer.clear();
+        if self.stream()?.read_line(buffer).await? == 0 {
+            self.bufstream = None;
+            return Err(Error::Disconnected);
+        }
+        Ok(())
+    }
+
+    async fn read_binary_data(&mut self, size: usize) -> Result<Vec<u8>> {
+        let mut binary = vec![0u8; size];
+        self.stream()?.read_exact(&mut binary).await?;
+
+        // Skip the newline after the binary data
+        let mut buffer = String::new();
+        self.read_line(&mut buffer).await?;
+
+        Ok(binary)
+    }
+
+    pub async fn command(&mut self, command: &str) -> Result<CommandResult> {
+        let mut results = self.request(command, false).await?;
+        Ok(results.remove(0))
+    }
+
+    /// Starts a command list, which sends several commands to MPD in a single
+    /// round trip. MPD executes them in order without handling other clients
+    /// in between, and stops at the first failing command.
+    pub fn command_list(&mut self) -> CommandList<'_> {
+        CommandList::new(self)
+    }
+
+    async fn request(&mut self, request: &str, list: bool) -> Result<Vec<CommandResult>> {
+        if !self.is_connected() {
+            self.connect().await?;
+        }
+
+        match self.send_request(request, list).await {
+            Err(Error::Io(_) | Error::Disconnected) if request.lines().all(Self::is_idempotent) => {
+                self.connect().await?;
+                self.send_request(request, list).await
+            }
+            result => result,
+        }
+    }
+
+    async fn send_command(&mut self, command: &str) -> Result<CommandResult> {
+        let mut results = self.send_request(command, false).await?;
+        Ok(results.remove(0))
+    }
+
+    async fn send_request(&mut self, request: &str, list: bool) -> Result<Vec<CommandResult>> {
+        let result = self.send_request_inner(request, list).await;
+
+        // After an I/O error or an unexpected response we no longer know
+        // where we are in the stream, so start over with a new connection
+        if let Err(Error::Io(_) | Error::Protocol(_)) = result {
+            self.bufstream = None;
+        }
+
+        result
+    }
+
+    async fn send_request_inner(
+        &mut self,
+        request: &str,
+        list: bool,
+    ) -> Result<Vec<CommandResult>> {
+        let stream = self.stream()?;
+        stream.write_all(format!("{request}\n").as_bytes()).await?;
+        stream.flush().await?;
+
+        let mut results = Vec::new();
+        let mut properties = Vec::new();
+        let mut binary = None;
+        let mut buffer = String::new();
+        loop {
+            self.read_line(&mut buffer).await?;
+
+            if let Some((key, value)) = buffer.split_once(": ") {
+                let value = value.trim_end();
+                properties.push((key.to_string(), value.to_string()));
+
+                if key == "binary" {
+                    let size = value
+                        .parse()
+                        .map_err(|_| Error::Protocol(format!("invalid binary size '{value}'")))?;
+                    binary = Some(self.read_binary_data(size).await?);
+                }
+            } else if list && buffer.starts_with("list_OK") {
+                results.push(CommandResult {
+                    properties: std::mem::take(&mut properties),
+                    binary: binary.take(),
+                });
+            } else if buffer.starts_with("OK") {
+                if !list {
+                    results.push(CommandResult { properties, binary });
+                }
+                return Ok(results);
+            } else if buffer.starts_with("ACK") {
+                return Err(Error::from_ack(&buffer));
+            } else {
+                return Err(Error::Protocol(format!(
+                    "unexpected response '{}'",
+                    buffer.trim_end()
+                )));
+            }
+        }
+    }
+
+    pub async fn command_binary(&mut self, command: &str) -> Result<CommandResult> {
+        let mut buffer = Vec::new();
+
+        loop {
+            let command = format!("{} {}", command, buffer.len());
+            let result = self.command(&command).await?;
+
+            if let Some(mut binary) = result.binary {
+                if !binary.is_empty() {
+                    buffer.append(&mut binary);
+                } else {
+                    return Ok(CommandResult::new_binary(result.properties, buffer));
+                }
+            } else {
+                return Ok(CommandResult::new(result.properties));
+            }
+        }
+    }
+
+    /// Checks that the connection is still alive, without reconnecting.
+    pub async fn ping(&mut self) -> Result<()> {
+        self.send_request("ping", false).await?;
+        Ok(())
+    }
+
+    pub async fn play(&mut self, position: Option<&str>) -> Result<()> {
+        self.command(&play_command(position)).await?;
+        Ok(())
+    }
+
+    pub async fn idle(&mut self, systems: &[&str]) -> Result<Vec<String>> {
+        let systems = systems.join(" ");
+        let result = self.command(&format!("idle {systems}")).await?;
+        let changed = result
+            .properties
+            .iter()
+            .filter(|(key, _)| key == "changed")
+            .map(|(_, value)| value.clone())
+            .collect();
+        Ok(changed)
+    }
+
+    pub async fn albumart(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+        if !self.capabilities.supports(Feature::AlbumArt) {
+            return Ok(None);
+        }
+
+        let path = Self::escape_str(path);
+        let result = self
+            .command_binary(&format!(r#"albumart "{path}""#))
+            .await?;
+
+        Ok(result.binary)
+    }
+
+    pub async fn readpicture(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+        if !self.capabilities.supports(Feature::ReadPicture) {
+            return Ok(None);
+        }
+
+        let path = Self::escape_str(path);
+        let result = self
+            .command_binary(&format!(r#"readpicture "{path}""#))
+            .await?;
+
+        Ok(result.binary)
+    }
+
+    pub async fn status(&mut self) -> Result<Status> {
+        self.command("status").await?.into_status()
+    }
+
+    /// Returns the volume, or `None` if MPD has no mixer.
+    pub async fn volume(&mut self) -> Result<Option<u32>> {
+        if !self.capabilities.supports(Feature::GetVol) {
+            return Ok(self.status().await?.volume);
+        }
+
+        match self.command("getvol").await {
+            Ok(result) => Ok(result
+                .properties
+                .iter()
+                .find(|(key, _)| key == "volume")
+                .and_then(|(_, volume)| volume.parse().ok())),
+            Err(Error::Ack {
+                code: AckCode::System,
+                ..
+            }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn set_volume(&mut self, volume: u32) -> Result<()> {
+        self.command(&format!("setvol {}", volume.min(100))).await?;
+        Ok(())
+    }
+
+    /// Changes the volume relative to the current volume.
+    pub async fn change_volume(&mut self, change: i32) -> Result<()> {
+        self.command(&format!("volume {change:+}")).await?;
+        Ok(())
+    }
+
+    pub async fn stats(&mut self) -> Result<Stats> {
+        self.command("stats").await?.into_stats()
+    }
+
+    pub async fn ls(&mut self, path: &str) -> Result<Vec<Entry>> {
+        fn get_filename(path: &str) -> String {
+            std::path::Path::new(path)
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or("n/a".to_string())
+        }
+
+        let path = Self::escape_str(path);
+        let result = self
+            .command(&format!(r#"lsinfo "{path}""#))
+            .await?
+            .into_groups(&["file", "directory", "playlist"]);
+
+        let mut entries = Vec::with_capacity(result.len());
+        for properties in result {
+            let (key, value) = &properties[0];
+            let entry = match key.as_str() {
+                "file" => Song::from_properties(properties)?.into(),
+                "directory" => Entry::Directory {
+                    name: get_filename(value),
+                    path: value.to_string(),
+                },
+                "playlist" => Entry::Playlist {
+                    name: get_filename(value),
+                    path: value.to_string(),
+                },
+                _ => unreachable!(),
+            };
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    /// Searches the database for songs matching `filter`. `search` compares
+    /// case-insensitively, `find` (with `exact`) case-sensitively.
+    pub async fn search(
+        &mut self,
+        filter: &Filter,
+        exact: bool,
+        window: Option<Range<usize>>,
+    ) -> Result<Vec<Song>> {
+        let command = if exact { "find" } else { "search" };
+        let filter = Self::escape_str(&filter.to_string());
+        let mut command = format!(r#"{command} "{filter}""#);
+        if let Some(window) = window {
+            command += &format!(" window {}:{}", window.start, window.end);
+        }
+
+        self.command(&command).await?.into_songs()
+    }
+
+    /// Lists the unique values of `tag`, optionally only for songs matching
+    /// `filter`. Songs without the tag are skipped.
+    pub async fn list(&mut self, tag: &str, filter: Option<&Filter>) -> Result<Vec<String>> {
+        let mut command = format!("list {tag}");
+        if let Some(filter) = filter {
+            let filter = Self::escape_str(&filter.to_string());
+            command += &format!(r#" "{filter}""#);
+        }
+
+        let values = self
+            .command(&command)
+            .await?
+            .properties
+            .into_iter()
+            .filter(|(key, value)| key.eq_ignore_ascii_case(tag) && !value.is_empty())
+            .map(|(_, value)| value)
+            .collect();
+        Ok(values)
+    }
+
+    /// Adds all songs matching `filter` to the queue.
+    pub async fn search_add(&mut self, filter: &Filter, exact: bool) -> Result<()> {
+        let feature = if exact {
+            Feature::FindAdd
+        } else {
+            Feature::SearchAdd
+        };
+        if !self.capabilities.supports(feature) {
+            return Err(Error::Unsupported(feature));
+        }
+
+        let command = if exact { "findadd" } else { "searchadd" };
+        let filter = Self::escape_str(&filter.to_string());
+        self.command(&format!(r#"{command} "{filter}""#)).await?;
+        Ok(())
+    }
+
+    /// Returns the queue items that were added or changed since queue
+    /// `version`, the whole queue for version 0. Items beyond the current
+    /// queue length were removed.
+    pub async fn queue_changes(&mut self, version: u32) -> Result<Vec<Song>> {
+        self.command(&format!("plchanges {version}"))
+            .await?
+            .into_songs()
+    }
+
+    /// Returns the queue items within `range`, or with `since` set to an
+    /// earlier queue version, only those that were added or changed since
+    /// then.
+    pub async fn playlist(&mut self, since: Option<u32>, range: Range<u32>) -> Result<Queue> {
+        let songs = match since {
+            Some(version) if self.capabilities.supports(Feature::PlchangesRange) => {
+                format!("plchanges {version} {}:{}", range.start, range.end)
+            }
+            Some(version) => format!("plchanges {version}"),
+            None => format!("playlistinfo {}:{}", range.start, range.end),
+        };
+
+        let mut list = self.command_list();
+        list.command("status").command(songs);
+        let [status, songs] = list
+            .run()
+            .await?
+            .try_into()
+            .expect("command list returns one result per command");
+
+        let status = status.into_status()?;
+        let items = songs
+            .into_songs()?
+            .into_iter()
+            .filter_map(|song| QueueItem::from_song(song, status.song))
+            .filter(|item| range.contains(&item.position))
+            .collect();
+
+        Ok(Queue {
+            version: status.playlist,
+            length: status.playlist_length,
+            current: status.song,
+            items,
+        })
+    }
+
+    /// Returns the queue items at the given positions, skipping positions
+    /// past the end of the queue.
+    pub async fn queue_items(
+        &mut self,
+        positions: &[u32],
+        current: Option<u32>,
+    ) -> Result<Vec<QueueItem>> {
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+
+        loop {
+            if positions.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mut list = self.command_list();
+            for position in &positions {
+                list.command(format!("playlistinfo {position}"));
+            }
+
+            match list.run().await {
+                Ok(results) => {
+                    let mut items = Vec::new();
+                    for result in results {
+                        if let Some(song) = result.into_song()? {
+                            items.extend(QueueItem::from_song(song, current));
+                        }
+                    }
+                    return Ok(items);
+                }
+                // The queue got shorter, so this position and the ones after
+                // it are past its end now
+                Err(Error::Ack {
+                    code: AckCode::Arg,
+                    list_num,
+                    ..
+                }) if (list_num as usize) < positions.len() => {
+                    positions.truncate(list_num as usize);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl QueueItem {
+    /// Returns `None` if the song is not in the queue.
+    pub fn from_song(song: Song, current: Option<u32>) -> Option<Self> {
+        Some(Self {
+            id: song.id?,
+            position: song.position?,
+            title: song.display_title(),
+            artist: song.artist(),
+            playing: song.position.is_some() && song.position == current,
+            file: song.file,
+        })
+    }
+}
+
+impl From<Song> for Entry {
+    fn from(song: Song) -> Self {
+        Entry::Song {
+            track: song.track(),
+            name: song.display_title(),
+            artist: song.artist().unwrap_or_default(),
+            path: song.file,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idempotent_commands_are_sorted() {
+        assert!(IDEMPOTENT_COMMANDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn only_pause_with_an_argument_is_idempotent() {
+        assert!(Mpd::is_idempotent("pause 1"));
+        assert!(Mpd::is_idempotent("pause 0"));
+        assert!(!Mpd::is_idempotent("pause"));
+        assert!(Mpd::is_idempotent("setvol 50"));
+        assert!(!Mpd::is_idempotent("next"));
+    }
+}
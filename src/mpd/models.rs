@@ -0,0 +1,445 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{Error, Result};
+
+type Properties = Vec<(String, String)>;
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Protocol(format!("invalid value '{value}' for '{key}'")))
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Error::Protocol(format!(
+            "invalid value '{value}' for '{key}'"
+        ))),
+    }
+}
+
+/// Parses numbers like the `Track` and `Disc` tags, which may look like `3/12`.
+fn parse_leading_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerState {
+    Play,
+    Pause,
+    #[default]
+    Stop,
+}
+
+impl FromStr for PlayerState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "play" => Ok(Self::Play),
+            "pause" => Ok(Self::Pause),
+            "stop" => Ok(Self::Stop),
+            _ => Err(Error::Protocol(format!("invalid player state '{s}'"))),
+        }
+    }
+}
+
+/// The value of `single`, and of `consume` since MPD 0.24.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SingleMode {
+    #[default]
+    Off,
+    On,
+    Oneshot,
+}
+
+impl SingleMode {
+    pub fn is_enabled(self) -> bool {
+        self != Self::Off
+    }
+}
+
+impl FromStr for SingleMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "0" => Ok(Self::Off),
+            "1" => Ok(Self::On),
+            "oneshot" => Ok(Self::Oneshot),
+            _ => Err(Error::Protocol(format!("invalid single mode '{s}'"))),
+        }
+    }
+}
+
+/// An audio format such as `44100:16:2`, `48000:f:2` or `dsd64:2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AudioFormat {
+    pub sample_rate: String,
+    pub bits: Option<String>,
+    pub channels: String,
+}
+
+impl FromStr for AudioFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<_> = s.split(':').collect();
+        match parts[..] {
+            [sample_rate, bits, channels] => Ok(Self {
+                sample_rate: sample_rate.to_string(),
+                bits: Some(bits.to_string()),
+                channels: channels.to_string(),
+            }),
+            [sample_rate, channels] => Ok(Self {
+                sample_rate: sample_rate.to_string(),
+                bits: None,
+                channels: channels.to_string(),
+            }),
+            _ => Err(Error::Protocol(format!("invalid audio format '{s}'"))),
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.bits {
+            Some(bits) => write!(f, "{}:{}:{}", self.sample_rate, bits, self.channels),
+            None => write!(f, "{}:{}", self.sample_rate, self.channels),
+        }
+    }
+}
+
+/// The output of the `status` command.
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct Status {
+    pub partition: Option<String>,
+    /// `None` if MPD has no mixer
+    pub volume: Option<u32>,
+    pub repeat: bool,
+    pub random: bool,
+    pub single: SingleMode,
+    pub consume: SingleMode,
+    /// The queue version, which changes whenever the queue is modified
+    pub playlist: u32,
+    pub playlist_length: u32,
+    pub state: PlayerState,
+    pub song: Option<u32>,
+    pub song_id: Option<u32>,
+    pub next_song: Option<u32>,
+    pub next_song_id: Option<u32>,
+    pub elapsed: Option<f64>,
+    pub duration: Option<f64>,
+    /// In kbit/s
+    pub bitrate: Option<u32>,
+    /// In seconds
+    pub xfade: Option<u32>,
+    pub mixramp_db: Option<f64>,
+    pub mixramp_delay: Option<f64>,
+    pub audio: Option<AudioFormat>,
+    pub updating_db: Option<u32>,
+    pub error: Option<String>,
+}
+
+impl Status {
+    pub fn from_properties(properties: Properties) -> Result<Self> {
+        let mut status = Self::default();
+
+        for (key, value) in properties {
+            let value = value.as_str();
+            match key.as_str() {
+                "partition" => status.partition = Some(value.to_string()),
+                "volume" => status.volume = value.parse().ok(),
+                "repeat" => status.repeat = parse_flag(&key, value)?,
+                "random" => status.random = parse_flag(&key, value)?,
+                "single" => status.single = value.parse()?,
+                "consume" => status.consume = value.parse()?,
+                "playlist" => status.playlist = parse(&key, value)?,
+                "playlistlength" => status.playlist_length = parse(&key, value)?,
+                "state" => status.state = value.parse()?,
+                "song" => status.song = Some(parse(&key, value)?),
+                "songid" => status.song_id = Some(parse(&key, value)?),
+                "nextsong" => status.next_song = Some(parse(&key, value)?),
+                "nextsongid" => status.next_song_id = Some(parse(&key, value)?),
+                "elapsed" => status.elapsed = Some(parse(&key, value)?),
+                "duration" => status.duration = Some(parse(&key, value)?),
+                "bitrate" => status.bitrate = Some(parse(&key, value)?),
+                "xfade" => status.xfade = Some(parse(&key, value)?),
+                "mixrampdb" => status.mixramp_db = Some(parse(&key, value)?),
+                "mixrampdelay" => status.mixramp_delay = Some(parse(&key, value)?),
+                "audio" => status.audio = Some(value.parse()?),
+                "updating_db" => status.updating_db = Some(parse(&key, value)?),
+                "error" => status.error = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(status)
+    }
+}
+
+/// A song, either from the database or from the queue, in which case
+/// `position` and `id` are set.
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct Song {
+    pub file: String,
+    pub last_modified: Option<String>,
+    pub format: Option<AudioFormat>,
+    pub duration: Option<f64>,
+    pub position: Option<u32>,
+    pub id: Option<u32>,
+    pub priority: Option<u32>,
+    /// All tags, keyed by their name as MPD sends it (e.g. `Artist` or
+    /// `MUSICBRAINZ_TRACKID`). Tags can occur multiple times, e.g. a song
+    /// with several artists.
+    pub tags: HashMap<String, Vec<String>>,
+}
+
+impl Song {
+    pub fn from_properties(properties: Properties) -> Result<Self> {
+        let mut song = Self::default();
+
+        for (key, value) in properties {
+            match key.as_str() {
+                "file" => song.file = value,
+                "Last-Modified" => song.last_modified = Some(value),
+                "Format" => song.format = value.parse().ok(),
+                "duration" => song.duration = Some(parse(&key, &value)?),
+                "Time" => {
+                    if song.duration.is_none() {
+                        song.duration = value.parse().ok();
+                    }
+                }
+                "Pos" => song.position = Some(parse(&key, &value)?),
+                "Id" => song.id = Some(parse(&key, &value)?),
+                "Prio" => song.priority = Some(parse(&key, &value)?),
+                "Added" | "Range" => {}
+                _ => song.tags.entry(key).or_default().push(value),
+            }
+        }
+
+        if song.file.is_empty() {
+            return Err(Error::Protocol("song without a file".into()));
+        }
+
+        Ok(song)
+    }
+
+    /// Returns the first value of a tag.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name)?.first().map(String::as_str)
+    }
+
+    /// Returns all values of a tag.
+    pub fn tag_values(&self, name: &str) -> &[String] {
+        self.tags.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.tag("Title")
+    }
+
+    /// The title, falling back to the stream name and then the file name.
+    pub fn display_title(&self) -> String {
+        self.title()
+            .or_else(|| self.tag("Name"))
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                std::path::Path::new(&self.file)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| self.file.clone())
+            })
+    }
+
+    pub fn artists(&self) -> &[String] {
+        self.tag_values("Artist")
+    }
+
+    /// All artists joined together, or `None` if there are none.
+    pub fn artist(&self) -> Option<String> {
+        let artists = self.artists();
+        (!artists.is_empty()).then(|| artists.join(", "))
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.tag("Album")
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.tag("Date")
+    }
+
+    pub fn track(&self) -> Option<u32> {
+        self.tag("Track").and_then(parse_leading_number)
+    }
+
+    pub fn disc(&self) -> Option<u32> {
+        self.tag("Disc").and_then(parse_leading_number)
+    }
+}
+
+/// The output of the `stats` command.
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct Stats {
+    pub artists: u64,
+    pub albums: u64,
+    pub songs: u64,
+    /// Seconds since MPD started
+    pub uptime: u64,
+    /// Sum of all song durations in the database, in seconds
+    pub db_playtime: u64,
+    /// UNIX timestamp of the last database update
+    pub db_update: u64,
+    /// Seconds MPD has been playing
+    pub playtime: u64,
+}
+
+impl Stats {
+    pub fn from_properties(properties: Properties) -> Result<Self> {
+        let mut stats = Self::default();
+
+        for (key, value) in properties {
+            let field = match key.as_str() {
+                "artists" => &mut stats.artists,
+                "albums" => &mut stats.albums,
+                "songs" => &mut stats.songs,
+                "uptime" => &mut stats.uptime,
+                "db_playtime" => &mut stats.db_playtime,
+                "db_update" => &mut stats.db_update,
+                "playtime" => &mut stats.playtime,
+                _ => continue,
+            };
+            *field = parse(&key, &value)?;
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(pairs: &[(&str, &str)]) -> Properties {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_status() {
+        let status = Status::from_properties(properties(&[
+            ("volume", "-1"),
+            ("repeat", "1"),
+            ("random", "0"),
+            ("single", "oneshot"),
+            ("consume", "0"),
+            ("playlist", "42"),
+            ("playlistlength", "3"),
+            ("state", "pause"),
+            ("song", "1"),
+            ("songid", "7"),
+            ("elapsed", "12.345"),
+            ("duration", "200.5"),
+            ("audio", "44100:16:2"),
+            ("unknown", "ignored"),
+        ]))
+        .unwrap();
+
+        assert_eq!(status.volume, None);
+        assert!(status.repeat);
+        assert!(!status.random);
+        assert_eq!(status.single, SingleMode::Oneshot);
+        assert!(!status.consume.is_enabled());
+        assert_eq!(status.playlist, 42);
+        assert_eq!(status.playlist_length, 3);
+        assert_eq!(status.state, PlayerState::Pause);
+        assert_eq!((status.song, status.song_id), (Some(1), Some(7)));
+        assert_eq!(status.elapsed, Some(12.345));
+        assert_eq!(status.duration, Some(200.5));
+        assert_eq!(status.audio.unwrap().to_string(), "44100:16:2");
+    }
+
+    #[test]
+    fn defaults_missing_status_fields() {
+        let status = Status::from_properties(Vec::new()).unwrap();
+        assert_eq!(status.state, PlayerState::Stop);
+        assert_eq!(status.song, None);
+        assert_eq!(status.volume, None);
+    }
+
+    #[test]
+    fn rejects_invalid_status_values() {
+        for pair in [
+            ("repeat", "2"),
+            ("single", "yes"),
+            ("state", "playing"),
+            ("song", "-1"),
+            ("elapsed", "soon"),
+            ("audio", "44100"),
+        ] {
+            assert!(
+                Status::from_properties(properties(&[pair])).is_err(),
+                "{pair:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_songs() {
+        let song = Song::from_properties(properties(&[
+            ("file", "Artist/Album/01.flac"),
+            ("Last-Modified", "2024-01-01T00:00:00Z"),
+            ("Format", "96000:24:2"),
+            ("Time", "181"),
+            ("duration", "180.5"),
+            ("Artist", "A"),
+            ("Artist", "B"),
+            ("Title", "Song"),
+            ("Track", "3/12"),
+            ("Disc", "1"),
+            ("Pos", "4"),
+            ("Id", "9"),
+            ("Added", "2024-01-01T00:00:00Z"),
+        ]))
+        .unwrap();
+
+        assert_eq!(song.file, "Artist/Album/01.flac");
+        assert_eq!(song.duration, Some(180.5));
+        assert_eq!(song.format.as_ref().unwrap().bits.as_deref(), Some("24"));
+        assert_eq!(song.artist().as_deref(), Some("A, B"));
+        assert_eq!(song.title(), Some("Song"));
+        assert_eq!(song.track(), Some(3));
+        assert_eq!(song.disc(), Some(1));
+        assert_eq!((song.position, song.id), (Some(4), Some(9)));
+        assert!(!song.tags.contains_key("Added"));
+    }
+
+    #[test]
+    fn falls_back_to_time_and_file_name() {
+        let song = Song::from_properties(properties(&[
+            ("file", "http://radio.example/stream.mp3"),
+            ("Time", "60"),
+        ]))
+        .unwrap();
+        assert_eq!(song.duration, Some(60.0));
+        assert_eq!(song.display_title(), "stream.mp3");
+        assert_eq!(song.artist(), None);
+        assert_eq!(song.track(), None);
+    }
+
+    #[test]
+    fn rejects_songs_without_a_file() {
+        assert!(Song::from_properties(properties(&[("Title", "Song")])).is_err());
+        assert!(Song::from_properties(properties(&[("file", "a"), ("Id", "x")])).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{Error, Mpd, Result};
+
+/// An audio output as listed by `outputs`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Output {
+    pub id: u32,
+    pub name: String,
+    pub plugin: Option<String>,
+    pub enabled: bool,
+    /// Runtime attributes that can be changed with `outputset`
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Output {
+    fn from_properties(properties: Vec<(String, String)>) -> Result<Self> {
+        let mut id = None;
+        let mut output = Output {
+            id: 0,
+            name: String::new(),
+            plugin: None,
+            enabled: false,
+            attributes: BTreeMap::new(),
+        };
+
+        for (key, value) in properties {
+            match key.as_str() {
+                "outputid" => id = value.parse().ok(),
+                "outputname" => output.name = value,
+                "plugin" => output.plugin = Some(value),
+                "outputenabled" => output.enabled = value == "1",
+                "attribute" => {
+                    if let Some((name, value)) = value.split_once('=') {
+                        output
+                            .attributes
+                            .insert(name.to_string(), value.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        output.id = id.ok_or_else(|| Error::Protocol("output without an id".into()))?;
+        Ok(output)
+    }
+}
+
+impl Mpd {
+    pub async fn outputs(&mut self) -> Result<Vec<Output>> {
+        self.command("outputs")
+            .await?
+            .into_groups(&["outputid"])
+            .into_iter()
+            .map(Output::from_properties)
+            .collect()
+    }
+
+    pub async fn enable_output(&mut self, id: u32) -> Result<()> {
+        self.command(&format!("enableoutput {id}")).await?;
+        Ok(())
+    }
+
+    pub async fn disable_output(&mut self, id: u32) -> Result<()> {
+        self.command(&format!("disableoutput {id}")).await?;
+        Ok(())
+    }
+
+    pub async fn toggle_output(&mut self, id: u32) -> Result<()> {
+        self.command(&format!("toggleoutput {id}")).await?;
+        Ok(())
+    }
+
+    /// Sets a runtime attribute of an output (MPD 0.21+).
+    pub async fn set_output_attribute(&mut self, id: u32, name: &str, value: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        let value = Self::escape_str(value);
+        self.command(&format!(r#"outputset {id} "{name}" "{value}""#))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use super::{Mpd, Result};
+
+/// The name MPD gives the partition clients start out in.
+pub const DEFAULT_PARTITION: &str = "default";
+
+impl Mpd {
+    /// The partition this connection is bound to, `None` being the default one.
+    pub fn partition(&self) -> Option<&str> {
+        self.partition.as_deref()
+    }
+
+    /// Binds this connection to another partition, `None` being the default
+    /// one. The partition is selected again after reconnecting.
+    pub async fn switch_partition(&mut self, partition: Option<&str>) -> Result<()> {
+        let partition = partition.filter(|&partition| partition != DEFAULT_PARTITION);
+        if self.partition.as_deref() == partition {
+            return Ok(());
+        }
+
+        let name = Self::escape_str(partition.unwrap_or(DEFAULT_PARTITION));
+        self.command(&format!(r#"partition "{name}""#)).await?;
+        self.partition = partition.map(str::to_string);
+        Ok(())
+    }
+
+    /// Reconnects bound to the default partition. Unlike
+    /// [`Mpd::switch_partition`], this also works in the middle of a command,
+    /// e.g. to leave a partition while waiting in `idle`.
+    pub async fn leave_partition(&mut self) -> Result<()> {
+        self.partition = None;
+        self.reconnect().await
+    }
+
+    pub async fn partitions(&mut self) -> Result<Vec<String>> {
+        let result = self.command("listpartitions").await?;
+        Ok(result
+            .properties
+            .into_iter()
+            .filter(|(key, _)| key == "partition")
+            .map(|(_, name)| name)
+            .collect())
+    }
+
+    pub async fn new_partition(&mut self, name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"newpartition "{name}""#)).await?;
+        Ok(())
+    }
+
+    pub async fn delete_partition(&mut self, name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"delpartition "{name}""#)).await?;
+        Ok(())
+    }
+
+    /// Moves an output into the partition this connection is bound to.
+    pub async fn move_output(&mut self, name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"moveoutput "{name}""#)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{Feature, Mpd, Result, Song};
+
+/// A playlist stored by MPD in its playlist directory.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StoredPlaylist {
+    pub name: String,
+    pub last_modified: Option<String>,
+    /// Only known when MPD supports `playlistlength` (0.24+)
+    pub songs: Option<u32>,
+}
+
+impl Mpd {
+    pub async fn playlists(&mut self) -> Result<Vec<StoredPlaylist>> {
+        let mut playlists: Vec<_> = self
+            .command("listplaylists")
+            .await?
+            .into_groups(&["playlist"])
+            .into_iter()
+            .map(|properties| {
+                let mut playlist = StoredPlaylist {
+                    name: String::new(),
+                    last_modified: None,
+                    songs: None,
+                };
+                for (key, value) in properties {
+                    match key.as_str() {
+                        "playlist" => playlist.name = value,
+                        "Last-Modified" => playlist.last_modified = Some(value),
+                        _ => {}
+                    }
+                }
+                playlist
+            })
+            .collect();
+        playlists.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if self.capabilities.supports(Feature::PlaylistLength) && !playlists.is_empty() {
+            let mut list = self.command_list();
+            for playlist in &playlists {
+                let name = Self::escape_str(&playlist.name);
+                list.command(format!(r#"playlistlength "{name}""#));
+            }
+
+            for (playlist, result) in playlists.iter_mut().zip(list.run().await?) {
+                playlist.songs = result
+                    .properties
+                    .iter()
+                    .find(|(key, _)| key == "songs")
+                    .and_then(|(_, songs)| songs.parse().ok());
+            }
+        }
+
+        Ok(playlists)
+    }
+
+    /// Returns the songs in a stored playlist, with `position` set to their
+    /// index in the playlist.
+    pub async fn playlist_songs(&mut self, name: &str) -> Result<Vec<Song>> {
+        let name = Self::escape_str(name);
+        let mut songs = self
+            .command(&format!(r#"listplaylistinfo "{name}""#))
+            .await?
+            .into_songs()?;
+
+        for (position, song) in songs.iter_mut().enumerate() {
+            song.position = Some(position as u32);
+        }
+
+        Ok(songs)
+    }
+
+    /// Saves the queue as a new stored playlist.
+    pub async fn save_playlist(&mut self, name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"save "{name}""#)).await?;
+        Ok(())
+    }
+
+    pub async fn rename_playlist(&mut self, name: &str, new_name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        let new_name = Self::escape_str(new_name);
+        self.command(&format!(r#"rename "{name}" "{new_name}""#))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_playlist(&mut self, name: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"rm "{name}""#)).await?;
+        Ok(())
+    }
+
+    /// Adds a song or directory to a stored playlist, creating it if needed.
+    pub async fn playlist_add(&mut self, name: &str, path: &str) -> Result<()> {
+        let name = Self::escape_str(name);
+        let path = Self::escape_str(path);
+        self.command(&format!(r#"playlistadd "{name}" "{path}""#))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn playlist_delete(&mut self, name: &str, position: u32) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"playlistdelete "{name}" {position}"#))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn playlist_move(&mut self, name: &str, from: u32, to: u32) -> Result<()> {
+        let name = Self::escape_str(name);
+        self.command(&format!(r#"playlistmove "{name}" {from} {to}"#))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::{ConnectionInfo, Mpd, Result};
+
+const DEFAULT_SIZE: usize = 4;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct IdleConnection {
+    mpd: Mpd,
+    since: Instant,
+}
+
+/// A bounded pool of authenticated MPD connections, so a slow request (e.g.
+/// a large `albumart` transfer) does not hold up every other request.
+pub struct Pool {
+    info: ConnectionInfo,
+    idle: Mutex<Vec<IdleConnection>>,
+    permits: Semaphore,
+    size: usize,
+    idle_timeout: Duration,
+}
+
+impl Pool {
+    pub fn new(info: ConnectionInfo, size: usize, idle_timeout: Duration) -> Self {
+        Self {
+            info,
+            idle: Mutex::new(Vec::with_capacity(size)),
+            permits: Semaphore::new(size),
+            size,
+            idle_timeout,
+        }
+    }
+
+    pub fn from_env(info: ConnectionInfo) -> Self {
+        let size = std::env::var("EMPEDE_POOL_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(DEFAULT_SIZE);
+        let idle_timeout = std::env::var("EMPEDE_POOL_IDLE_TIMEOUT")
+            .ok()
+            .and_then(|timeout| timeout.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT);
+
+        Self::new(info, size, idle_timeout)
+    }
+
+    /// Waits for a free slot in the pool and returns a connection that was
+    /// checked to be alive, connecting a new one if none are available. Idle
+    /// connections that died are dropped, and connecting is only tried once,
+    /// so requests fail fast rather than hold their slot while MPD is down.
+    /// The connection is moved to `partition` if it is bound to another one.
+    ///
+    /// The connection is authenticated with `password` if given, instead of
+    /// the server's password. As MPD cannot take permissions back from a
+    /// connection, only idle connections using the same password are reused.
+    pub async fn get(
+        &'static self,
+        partition: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<PooledMpd> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("pool semaphore is never closed");
+
+        let info = match password {
+            Some(password) => ConnectionInfo {
+                password: Some(password.to_string()),
+                ..self.info.clone()
+            },
+            None => self.info.clone(),
+        };
+
+        let mut mpd = loop {
+            match self.pop_idle(info.password.as_deref()) {
+                Some(IdleConnection { mut mpd, since }) => {
+                    if since.elapsed() < self.idle_timeout && mpd.ping().await.is_ok() {
+                        break PooledMpd::new(self, permit, mpd);
+                    }
+                }
+                None => {
+                    let mut mpd = Mpd::new(info);
+                    mpd.connect().await?;
+                    break PooledMpd::new(self, permit, mpd);
+                }
+            }
+        };
+
+        mpd.switch_partition(partition).await?;
+        Ok(mpd)
+    }
+
+    /// Closes the idle connections bound to `partition`, since MPD refuses
+    /// to delete a partition that still has clients.
+    pub fn close_partition(&self, partition: &str) {
+        self.idle
+            .lock()
+            .unwrap()
+            .retain(|idle| idle.mpd.partition() != Some(partition));
+    }
+
+    /// Takes the most recently used idle connection authenticated with
+    /// `password`.
+    fn pop_idle(&self, password: Option<&str>) -> Option<IdleConnection> {
+        let mut idle = self.idle.lock().unwrap();
+        let index = idle
+            .iter()
+            .rposition(|idle| idle.mpd.info.password.as_deref() == password)?;
+        Some(idle.remove(index))
+    }
+
+    fn put_idle(&self, mpd: Mpd) {
+        let since = Instant::now();
+        let mut idle = self.idle.lock().unwrap();
+        // Connections with other passwords can pile up, keep the newest
+        if idle.len() >= self.size {
+            idle.remove(0);
+        }
+        idle.push(IdleConnection { mpd, since });
+    }
+}
+
+/// A connection checked out from a [`Pool`]. It is returned to the pool when
+/// dropped, unless it has been disconnected.
+pub struct PooledMpd {
+    pool: &'static Pool,
+    _permit: SemaphorePermit<'static>,
+    mpd: Option<Mpd>,
+}
+
+impl PooledMpd {
+    fn new(pool: &'static Pool, permit: SemaphorePermit<'static>, mpd: Mpd) -> Self {
+        Self {
+            pool,
+            _permit: permit,
+            mpd: Some(mpd),
+        }
+    }
+}
+
+impl Deref for PooledMpd {
+    type Target = Mpd;
+
+    fn deref(&self) -> &Mpd {
+        self.mpd.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledMpd {
+    fn deref_mut(&mut self) -> &mut Mpd {
+        self.mpd.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledMpd {
+    fn drop(&mut self) {
+        if let Some(mpd) = self.mpd.take() {
+            if mpd.is_connected() {
+                self.pool.put_idle(mpd);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::mpd::connection::Address;
+
+    /// Serves an MPD that accepts every command, returning its address and
+    /// the number of connections made so far.
+    async fn fake_mpd() -> (ConnectionInfo, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    stream.write_all(b"OK MPD 0.23.5\n").await?;
+                    let mut line = String::new();
+                    while stream.read_line(&mut line).await? > 0 {
+                        stream.write_all(b"OK\n").await?;
+                        line.clear();
+                    }
+                    std::io::Result::Ok(())
+                });
+            }
+        });
+
+        let info = ConnectionInfo {
+            address: Address::Tcp {
+                host: "127.0.0.1".into(),
+                port,
+            },
+            password: None,
+        };
+        (info, connections)
+    }
+
+    fn pool(info: ConnectionInfo, size: usize, idle_timeout: Duration) -> &'static Pool {
+        Box::leak(Box::new(Pool::new(info, size, idle_timeout)))
+    }
+
+    fn unconnected(password: &str, partition: Option<&str>) -> Mpd {
+        let mut mpd = Mpd::new(ConnectionInfo {
+            address: Address::Abstract("mpd".into()),
+            password: Some(password.into()),
+        });
+        mpd.partition = partition.map(str::to_string);
+        mpd
+    }
+
+    fn idle_passwords(pool: &Pool) -> Vec<String> {
+        let idle = pool.idle.lock().unwrap();
+        idle.iter()
+            .filter_map(|idle| idle.mpd.info.password.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reuses_idle_connections_with_the_same_password() {
+        let (info, connections) = fake_mpd().await;
+        let pool = pool(info, 2, DEFAULT_IDLE_TIMEOUT);
+
+        drop(pool.get(None, None).await.unwrap());
+        drop(pool.get(Some("other"), None).await.unwrap());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        let mpd = pool.get(None, Some("secret")).await.unwrap();
+        assert_eq!(mpd.info.password.as_deref(), Some("secret"));
+        drop(mpd);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        drop(pool.get(None, None).await.unwrap());
+        drop(pool.get(None, Some("secret")).await.unwrap());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn replaces_expired_idle_connections() {
+        let (info, connections) = fake_mpd().await;
+        let pool = pool(info, 2, Duration::ZERO);
+
+        drop(pool.get(None, None).await.unwrap());
+        drop(pool.get(None, None).await.unwrap());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_fast_while_mpd_is_unreachable() {
+        let info = ConnectionInfo::parse("/nonexistent/mpd.socket", 6600);
+        let pool = pool(info, 1, DEFAULT_IDLE_TIMEOUT);
+
+        let started = Instant::now();
+        assert!(pool.get(None, None).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // The slot was given back
+        assert_eq!(pool.permits.available_permits(), 1);
+    }
+
+    #[test]
+    fn keeps_at_most_size_idle_connections() {
+        let pool = Pool::new(
+            ConnectionInfo::parse("localhost", 6600),
+            2,
+            DEFAULT_IDLE_TIMEOUT,
+        );
+        for password in ["a", "b", "c"] {
+            pool.put_idle(unconnected(password, None));
+        }
+        assert_eq!(idle_passwords(&pool), ["b", "c"]);
+
+        assert!(pool.pop_idle(Some("a")).is_none());
+        assert!(pool.pop_idle(Some("b")).is_some());
+        assert_eq!(idle_passwords(&pool), ["c"]);
+    }
+
+    #[test]
+    fn closes_idle_connections_in_a_partition() {
+        let pool = Pool::new(
+            ConnectionInfo::parse("localhost", 6600),
+            4,
+            DEFAULT_IDLE_TIMEOUT,
+        );
+        pool.put_idle(unconnected("a", Some("kitchen")));
+        pool.put_idle(unconnected("b", None));
+        pool.put_idle(unconnected("c", Some("kitchen2")));
+
+        pool.close_partition("kitchen");
+        assert_eq!(idle_passwords(&pool), ["b", "c"]);
+    }
+}
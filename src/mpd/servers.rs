@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Mutex, OnceLock},
+};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use tokio::sync::broadcast;
+
+use super::{ConnectionInfo, Pool, PooledMpd, Result};
+
+/// The name of the server configured with `MPD_HOST` when `EMPEDE_SERVERS`
+/// is not set.
+const DEFAULT_SERVER: &str = "default";
+
+static SERVERS: OnceLock<Vec<Server>> = OnceLock::new();
+
+/// What `encodeURIComponent` escapes, so server paths built by scripts match.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+/// A named MPD server, with its own connection pool.
+pub struct Server {
+    pub name: String,
+    pub info: ConnectionInfo,
+    pool: Pool,
+    /// The volume of each partition from before muting it, as MPD itself has
+    /// no notion of muting
+    unmuted_volumes: Mutex<HashMap<String, u32>>,
+    /// The names of partitions about to be deleted
+    deleted_partitions: broadcast::Sender<String>,
+}
+
+impl Server {
+    fn new(name: String, info: ConnectionInfo) -> Self {
+        let pool = Pool::from_env(info.clone());
+        Self {
+            name,
+            info,
+            pool,
+            unmuted_volumes: Mutex::default(),
+            deleted_partitions: broadcast::channel(16).0,
+        }
+    }
+
+    /// Parses `name=host[:port]`, where `host` follows the `MPD_HOST`
+    /// conventions. IPv6 addresses with a port go in brackets.
+    fn parse(entry: &str) -> Option<Self> {
+        let (name, host) = entry.split_once('=')?;
+        let (name, host) = (name.trim(), host.trim());
+        if name.is_empty() || host.is_empty() {
+            return None;
+        }
+
+        // The port follows the address, which follows the password
+        let address_start = host.find('@').filter(|&at| at > 0).map_or(0, |at| at + 1);
+        let (password, address) = host.split_at(address_start);
+
+        let (address, port) = if address.starts_with(['/', '@']) {
+            (address, None)
+        } else if address.starts_with('[') {
+            match address.split_once("]:") {
+                Some((ip, port)) => (&address[..ip.len() + 1], Some(port)),
+                None => (address, None),
+            }
+        } else if address.matches(':').count() == 1 {
+            let (host, port) = address.split_once(':')?;
+            (host, Some(port))
+        } else {
+            // A host name, or an IPv6 address without a port
+            (address, None)
+        };
+
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None => ConnectionInfo::default_port(),
+        };
+
+        Some(Self::new(
+            name.to_string(),
+            ConnectionInfo::parse(&format!("{password}{address}"), port),
+        ))
+    }
+
+    /// All configured servers, in the order they were listed in
+    /// `EMPEDE_SERVERS` (e.g. `kitchen=kitchen.local,office=secret@office:6601`),
+    /// or the single server from `MPD_HOST` and `MPD_PORT`.
+    pub fn all() -> &'static [Server] {
+        SERVERS.get_or_init(|| {
+            let servers: Vec<_> = std::env::var("EMPEDE_SERVERS")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .filter_map(|entry| {
+                    let server = Self::parse(entry);
+                    if server.is_none() {
+                        println!("Ignoring invalid entry '{entry}' in EMPEDE_SERVERS");
+                    }
+                    server
+                })
+                .collect();
+
+            if servers.is_empty() {
+                vec![Self::new(
+                    DEFAULT_SERVER.to_string(),
+                    ConnectionInfo::from_env(),
+                )]
+            } else {
+                servers
+            }
+        })
+    }
+
+    pub fn get(name: &str) -> Option<&'static Server> {
+        Self::all().iter().find(|server| server.name == name)
+    }
+
+    /// The path the pages of this server are served at, `/s/{name}/`.
+    pub fn path(&self) -> String {
+        format!("/s/{}/", utf8_percent_encode(&self.name, PATH_SEGMENT))
+    }
+
+    /// Remembers the volume of `partition` before muting it.
+    pub fn set_unmuted_volume(&self, partition: &str, volume: u32) {
+        self.unmuted_volumes
+            .lock()
+            .unwrap()
+            .insert(partition.to_string(), volume);
+    }
+
+    pub fn take_unmuted_volume(&self, partition: &str) -> Option<u32> {
+        self.unmuted_volumes.lock().unwrap().remove(partition)
+    }
+
+    /// Makes connections leave `partition` ahead of deleting it, since MPD
+    /// refuses to delete a partition that still has clients: idle pooled
+    /// connections are closed, and [`Server::partition_deleted`] resolves for
+    /// connections outside the pool.
+    pub fn close_partition(&self, partition: &str) {
+        self.pool.close_partition(partition);
+        let _ = self.deleted_partitions.send(partition.to_string());
+    }
+
+    /// Resolves once `partition` is about to be deleted, and never for the
+    /// default partition.
+    pub fn partition_deleted(&self, partition: Option<&str>) -> impl Future<Output = ()> {
+        let mut deleted = self.deleted_partitions.subscribe();
+        let partition = partition.map(str::to_string);
+        async move {
+            let Some(partition) = partition else {
+                return std::future::pending().await;
+            };
+            loop {
+                match deleted.recv().await {
+                    Ok(name) if name == partition => return,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return std::future::pending().await
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Returns a pooled connection bound to `partition`, or to the default
+    /// partition if `None`, authenticated with `password` or the server's
+    /// password if `None`.
+    pub async fn get_instance(
+        &'static self,
+        partition: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<PooledMpd> {
+        self.pool.get(partition, password).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpd::connection::Address;
+
+    fn parse(entry: &str) -> Option<(String, ConnectionInfo)> {
+        Server::parse(entry).map(|server| (server.name, server.info))
+    }
+
+    fn tcp(host: &str, port: u16, password: Option<&str>) -> ConnectionInfo {
+        ConnectionInfo {
+            address: Address::Tcp {
+                host: host.into(),
+                port,
+            },
+            password: password.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn parses_hosts_and_ports() {
+        let default = ConnectionInfo::default_port();
+        assert_eq!(
+            parse("kitchen=kitchen.local"),
+            Some(("kitchen".into(), tcp("kitchen.local", default, None)))
+        );
+        assert_eq!(
+            parse(" office = secret@office:6601 "),
+            Some(("office".into(), tcp("office", 6601, Some("secret"))))
+        );
+        assert_eq!(parse("x=host:port"), None);
+        assert_eq!(parse("=host"), None);
+        assert_eq!(parse("x="), None);
+        assert_eq!(parse("host"), None);
+    }
+
+    #[test]
+    fn keeps_colons_in_passwords() {
+        let default = ConnectionInfo::default_port();
+        assert_eq!(
+            parse("office=pa:ss@office"),
+            Some(("office".into(), tcp("office", default, Some("pa:ss"))))
+        );
+        assert_eq!(
+            parse("office=pa:ss@office:6601"),
+            Some(("office".into(), tcp("office", 6601, Some("pa:ss"))))
+        );
+    }
+
+    #[test]
+    fn parses_ipv6_addresses() {
+        let default = ConnectionInfo::default_port();
+        assert_eq!(
+            parse("x=[::1]:6601"),
+            Some(("x".into(), tcp("::1", 6601, None)))
+        );
+        assert_eq!(
+            parse("x=[::1]"),
+            Some(("x".into(), tcp("::1", default, None)))
+        );
+        assert_eq!(
+            parse("x=::1"),
+            Some(("x".into(), tcp("::1", default, None)))
+        );
+        assert_eq!(
+            parse("x=pw@[fe80::1]:6601"),
+            Some(("x".into(), tcp("fe80::1", 6601, Some("pw"))))
+        );
+    }
+
+    #[test]
+    fn does_not_take_ports_from_sockets() {
+        let (_, info) = parse("x=/run/mpd:sock").unwrap();
+        assert_eq!(info.address, Address::Unix("/run/mpd:sock".into()));
+        let (_, info) = parse("x=pw@/run/mpd/socket").unwrap();
+        assert_eq!(info.address, Address::Unix("/run/mpd/socket".into()));
+        assert_eq!(info.password.as_deref(), Some("pw"));
+        let (_, info) = parse("x=@mpd:1").unwrap();
+        assert_eq!(info.address, Address::Abstract("mpd:1".into()));
+    }
+
+    #[test]
+    fn paths_match_encode_uri_component() {
+        let server = |name: &str| Server::new(name.into(), tcp("localhost", 6600, None));
+        assert_eq!(server("living-room").path(), "/s/living-room/");
+        assert_eq!(server("a_b.c~d").path(), "/s/a_b.c~d/");
+        assert_eq!(server("a b/c").path(), "/s/a%20b%2Fc/");
+    }
+}
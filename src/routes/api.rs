@@ -0,0 +1,356 @@
+//! A JSON API for scripts and other clients, under `/api/v1`. Requests go to
+//! the first server, or to another one under `/api/v1/servers/{server}`.
+//!
+//! Reading state has its own JSON handlers here, while the handlers for
+//! actions, which return no content anyway, are shared with the web UI.
+
+use crate::{
+    mpd,
+    routes::{self, session::Session},
+};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::ServiceResponse,
+    get,
+    middleware::ErrorHandlerResponse,
+    web::{self, Json},
+    HttpResponse, Responder,
+};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::{ContentBuilder, Ref, ResponseBuilder},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Empede",
+        description = "Control MPD over HTTP. All paths are relative to `/api/v1`, \
+            which uses the first configured server; prefix them with \
+            `/api/v1/servers/{server}` to use another one."
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        get_servers,
+        get_status,
+        get_current_song,
+        get_queue,
+        get_browse,
+        get_search,
+        get_playlists,
+        get_playlist,
+        get_outputs,
+        get_partitions,
+        get_volume,
+        routes::controls::post_play,
+        routes::controls::post_pause,
+        routes::controls::post_previous,
+        routes::controls::post_next,
+        routes::controls::post_seek,
+        routes::controls::post_consume,
+        routes::controls::post_random,
+        routes::controls::post_repeat,
+        routes::controls::post_single,
+        routes::controls::post_shuffle,
+        routes::volume::post_volume,
+        routes::volume::post_volume_mute,
+        routes::queue::post_queue,
+        routes::queue::delete_queue,
+        routes::queue::post_queue_move,
+        routes::queue::post_queue_send,
+        routes::search::post_search_queue,
+        routes::library::post_library_queue,
+        routes::playlists::post_playlists,
+        routes::playlists::delete_playlist,
+        routes::playlists::post_playlist_rename,
+        routes::playlists::post_playlist_add,
+        routes::playlists::delete_playlist_song,
+        routes::playlists::post_playlist_move,
+        routes::playlists::post_playlist_load,
+        routes::outputs::post_output_enable,
+        routes::outputs::post_output_disable,
+        routes::outputs::post_output_toggle,
+        routes::outputs::post_output_attribute,
+        routes::outputs::post_output_move,
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&ErrorResponses)
+)]
+struct ApiDoc;
+
+/// Documents the [`ErrorBody`] every operation can fail with.
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let response = ResponseBuilder::new()
+            .description("Error")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ErrorBody")))
+                    .build(),
+            )
+            .build();
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [&mut item.get, &mut item.post, &mut item.delete];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .insert("default".into(), response.clone().into());
+            }
+        }
+    }
+}
+
+#[get("/api/openapi.json")]
+pub async fn get_openapi() -> impl Responder {
+    Json(ApiDoc::openapi())
+}
+
+/// Registers the API routes that operate on a single server.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_status)
+        .service(get_current_song)
+        .service(get_queue)
+        .service(get_browse)
+        .service(get_search)
+        .service(get_playlists)
+        .service(get_playlist)
+        .service(get_outputs)
+        .service(get_partitions)
+        .service(get_volume)
+        .service(routes::controls::post_play)
+        .service(routes::controls::post_pause)
+        .service(routes::controls::post_previous)
+        .service(routes::controls::post_next)
+        .service(routes::controls::post_seek)
+        .service(routes::controls::post_consume)
+        .service(routes::controls::post_random)
+        .service(routes::controls::post_repeat)
+        .service(routes::controls::post_single)
+        .service(routes::controls::post_shuffle)
+        .service(routes::volume::post_volume)
+        .service(routes::volume::post_volume_mute)
+        .service(routes::queue::post_queue)
+        .service(routes::queue::delete_queue)
+        .service(routes::queue::post_queue_move)
+        .service(routes::queue::post_queue_send)
+        .service(routes::search::post_search_queue)
+        .service(routes::library::post_library_queue)
+        .service(routes::playlists::post_playlists)
+        .service(routes::playlists::delete_playlist)
+        .service(routes::playlists::post_playlist_rename)
+        .service(routes::playlists::post_playlist_add)
+        .service(routes::playlists::delete_playlist_song)
+        .service(routes::playlists::post_playlist_move)
+        .service(routes::playlists::post_playlist_load)
+        .service(routes::outputs::post_output_enable)
+        .service(routes::outputs::post_output_disable)
+        .service(routes::outputs::post_output_toggle)
+        .service(routes::outputs::post_output_attribute)
+        .service(routes::outputs::post_output_move)
+        .service(routes::ws::get_ws);
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    error: ErrorObject,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorObject {
+    status: u16,
+    /// The MPD `ACK` error code, if MPD refused the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ack: Option<u32>,
+    message: String,
+}
+
+/// Replaces the body of every error response with an [`ErrorBody`], so
+/// clients always get the same JSON shape, whether the error came from MPD,
+/// from parsing the request or from a handler.
+pub fn json_error<B: MessageBody>(
+    res: ServiceResponse<B>,
+) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let status = res.status();
+    let error = res.response().error();
+    let ack = match error.and_then(|e| e.as_error::<mpd::Error>()) {
+        Some(mpd::Error::Ack { code, .. }) => Some(u32::from(*code)),
+        _ => None,
+    };
+    let message = error.map(ToString::to_string);
+
+    let (req, res) = res.into_parts();
+    // Handlers may explain what was wrong with the request in the body
+    let message = message.or_else(|| match res.into_body().try_into_bytes() {
+        Ok(body) if !body.is_empty() => Some(String::from_utf8_lossy(&body).into_owned()),
+        _ => None,
+    });
+    let message =
+        message.unwrap_or_else(|| status.canonical_reason().unwrap_or_default().to_string());
+
+    let body = ErrorBody {
+        error: ErrorObject {
+            status: status.as_u16(),
+            ack,
+            message,
+        },
+    };
+
+    let res = ServiceResponse::new(req, HttpResponse::build(status).json(body));
+    Ok(ErrorHandlerResponse::Response(
+        res.map_body(|_, body| EitherBody::right(body)),
+    ))
+}
+
+pub async fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().finish()
+}
+
+#[derive(Serialize, ToSchema)]
+struct ServerInfo {
+    name: &'static str,
+}
+
+#[utoipa::path(tag = "servers", responses((status = 200, body = [ServerInfo])))]
+#[get("/servers")]
+pub async fn get_servers() -> impl Responder {
+    let servers: Vec<_> = mpd::Server::all()
+        .iter()
+        .map(|server| ServerInfo { name: &server.name })
+        .collect();
+    Json(servers)
+}
+
+#[utoipa::path(tag = "player", responses((status = 200, body = mpd::Status)))]
+#[get("/status")]
+async fn get_status(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.status().await?))
+}
+
+/// The current song, or `null` if there is none.
+#[utoipa::path(tag = "player", responses((status = 200, body = Option<mpd::Song>)))]
+#[get("/currentsong")]
+async fn get_current_song(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.command("currentsong").await?.into_song()?))
+}
+
+#[utoipa::path(tag = "queue", responses((status = 200, body = [mpd::Song])))]
+#[get("/queue")]
+async fn get_queue(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.command("playlistinfo").await?.into_songs()?))
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+struct BrowseQuery {
+    path: String,
+}
+
+#[utoipa::path(
+    tag = "library",
+    params(BrowseQuery),
+    responses((status = 200, body = [mpd::Entry]))
+)]
+#[get("/browse")]
+async fn get_browse(
+    session: Session,
+    query: web::Query<BrowseQuery>,
+) -> mpd::Result<impl Responder> {
+    let path = percent_decode_str(&query.path).decode_utf8_lossy();
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.ls(&path).await?))
+}
+
+#[derive(Serialize, ToSchema)]
+struct SearchResults {
+    songs: Vec<mpd::Song>,
+    page: usize,
+    has_next: bool,
+}
+
+#[utoipa::path(
+    tag = "library",
+    params(routes::search::SearchQuery),
+    responses((status = 200, body = SearchResults))
+)]
+#[get("/search")]
+async fn get_search(
+    session: Session,
+    query: web::Query<routes::search::SearchQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let (songs, has_next) = routes::search::search_page(&mut mpd, &query).await?;
+    Ok(Json(SearchResults {
+        songs,
+        page: query.page,
+        has_next,
+    }))
+}
+
+#[utoipa::path(tag = "playlists", responses((status = 200, body = [mpd::StoredPlaylist])))]
+#[get("/playlists")]
+async fn get_playlists(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.playlists().await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PlaylistQuery {
+    name: String,
+}
+
+#[utoipa::path(
+    tag = "playlists",
+    params(PlaylistQuery),
+    responses((status = 200, body = [mpd::Song]))
+)]
+#[get("/playlist")]
+async fn get_playlist(
+    session: Session,
+    query: web::Query<PlaylistQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.playlist_songs(&query.name).await?))
+}
+
+#[utoipa::path(tag = "outputs", responses((status = 200, body = [mpd::Output])))]
+#[get("/outputs")]
+async fn get_outputs(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    Ok(Json(mpd.outputs().await?))
+}
+
+#[utoipa::path(tag = "partitions", responses((status = 200, body = [String])))]
+#[get("/partitions")]
+async fn get_partitions(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    if !mpd.capabilities().supports(mpd::Feature::Partitions) {
+        return Err(mpd::Error::Unsupported(mpd::Feature::Partitions));
+    }
+    Ok(Json(mpd.partitions().await?))
+}
+
+#[derive(Serialize, ToSchema)]
+struct Volume {
+    /// `null` if MPD has no mixer
+    volume: Option<u32>,
+}
+
+#[utoipa::path(tag = "volume", responses((status = 200, body = Volume)))]
+#[get("/volume")]
+async fn get_volume(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let volume = mpd.volume().await?;
+    Ok(Json(Volume { volume }))
+}
@@ -1,8 +1,14 @@
-use crate::mpd;
+use std::sync::Arc;
+
+use crate::{
+    art::{self, Album, Rendition},
+    mpd::{self, Filter},
+    routes::session::Session,
+};
 use actix_web::{
     get,
-    http::header::{self, CacheDirective},
-    web, HttpResponse, Responder,
+    http::header::{self, CacheDirective, EntityTag},
+    web, HttpRequest, HttpResponse,
 };
 use percent_encoding::percent_decode_str;
 use serde::Deserialize;
@@ -10,33 +16,210 @@ use serde::Deserialize;
 #[derive(Deserialize, Default)]
 #[serde(default)]
 struct ArtQuery {
+    /// A song, redirected to the picture of its album if it has one
     path: String,
+    /// The directory of an album
+    dir: Option<String>,
+    albumartist: Option<String>,
+    album: Option<String>,
+    /// The maximum width and height in pixels, the original picture if unset
+    size: Option<u32>,
+}
+
+/// Fetches a song's picture, looking for a cover file next to it in the
+/// local music directory first, then having MPD send one, and finally one
+/// embedded in the song. MPD refusing either counts as having no picture.
+async fn fetch(session: &Session, path: &str) -> mpd::Result<Option<Vec<u8>>> {
+    if let Some(art) = art::Resolver::global().local(path).await {
+        return Ok(Some(art));
+    }
+
+    let mut mpd = session.mpd().await?;
+    match mpd.albumart(path).await {
+        Ok(Some(art)) => Ok(Some(art)),
+        Ok(None) | Err(mpd::Error::Ack { .. }) => match mpd.readpicture(path).await {
+            Err(mpd::Error::Ack { .. }) => Ok(None),
+            result => result,
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Fetches the picture of one of the album's songs.
+async fn fetch_album(session: &Session, album: &Album) -> mpd::Result<Option<Vec<u8>>> {
+    let path = match album {
+        Album::Directory(directory) => {
+            if let Some(art) = art::Resolver::global().local(directory).await {
+                return Ok(Some(art));
+            }
+
+            let entries = session.mpd().await?.ls(directory).await?;
+            let song = entries.into_iter().find_map(|entry| match entry {
+                mpd::Entry::Song { path, .. } => Some(path),
+                _ => None,
+            });
+            match song {
+                Some(song) => song,
+                None => return Ok(None),
+            }
+        }
+        Album::Tags { artist, album } => {
+            let mut mpd = session.mpd().await?;
+            let tag = mpd.capabilities().album_artist_tag();
+            let filter = Filter::equals(tag, artist).and(Filter::equals("Album", album));
+            let songs = mpd.search(&filter, true, Some(0..1)).await?;
+            match songs.into_iter().next() {
+                Some(song) => song.file,
+                None => return Ok(None),
+            }
+        }
+        // Streams have no art, and MPD would have to open them to look
+        Album::Song(path) if path.contains("://") => return Ok(None),
+        Album::Song(path) => path.clone(),
+    };
+
+    fetch(session, &path).await
+}
+
+/// The album of a song in the root of the music directory, which can only
+/// be told from its tags.
+async fn album_by_tags(session: &Session, path: &str) -> mpd::Result<Option<Album>> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let filter = Filter::equals("file", path);
+    let songs = mpd.search(&filter, true, Some(0..1)).await?;
+    let Some(song) = songs.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let artist = song
+        .tag(tag)
+        .or_else(|| song.artists().first().map(String::as_str));
+    Ok(artist.zip(song.album()).map(|(artist, album)| Album::Tags {
+        artist: artist.to_string(),
+        album: album.to_string(),
+    }))
+}
+
+fn redirect(album: &Album, size: Option<u32>) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, album.url(size)))
+        .append_header(header::CacheControl(vec![CacheDirective::MaxAge(3600)]))
+        .finish()
+}
+
+fn placeholder() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("image/webp")
+        .append_header(header::CacheControl(vec![CacheDirective::MaxAge(3600)]))
+        .body(art::PLACEHOLDER)
 }
 
 #[get("/art")]
-pub async fn get_art(query: web::Query<ArtQuery>) -> impl Responder {
-    let path = percent_decode_str(&query.path).decode_utf8_lossy();
-    let mut mpd = mpd::get_instance().await;
-
-    if let Ok(art) = mpd.albumart(&path).await {
-        let mime = infer::get(&art)
-            .map(|k| k.mime_type())
-            .unwrap_or("application/octet-stream");
-
-        HttpResponse::Ok()
-            .content_type(mime)
-            .append_header(header::CacheControl(vec![CacheDirective::MaxAge(3600)]))
-            .body(art)
-    } else if let Ok(art) = mpd.readpicture(&path).await {
-        let mime = infer::get(&art)
-            .map(|k| k.mime_type())
-            .unwrap_or("application/octet-stream");
-
-        HttpResponse::Ok()
-            .content_type(mime)
-            .append_header(header::CacheControl(vec![CacheDirective::MaxAge(3600)]))
-            .body(art)
-    } else {
-        HttpResponse::NotFound().finish()
+pub async fn get_art(
+    req: HttpRequest,
+    session: Session,
+    query: web::Query<ArtQuery>,
+) -> mpd::Result<HttpResponse> {
+    let mut rendition = Rendition::new(query.size);
+    let cache = art::Cache::global();
+
+    let album = match (&query.dir, &query.albumartist, &query.album) {
+        (Some(directory), _, _) => Album::Directory(directory.clone()),
+        (None, Some(artist), Some(album)) => Album::Tags {
+            artist: artist.clone(),
+            album: album.clone(),
+        },
+        _ => {
+            // Songs of the same album share one URL, so browsers only fetch
+            // their picture once
+            let path = percent_decode_str(&query.path).decode_utf8_lossy();
+            match Album::from_path(&path) {
+                album @ Album::Directory(_) => return Ok(redirect(&album, query.size)),
+                Album::Song(path) if !path.contains("://") => {
+                    match album_by_tags(&session, &path).await? {
+                        Some(album) => return Ok(redirect(&album, query.size)),
+                        None => Album::Song(path),
+                    }
+                }
+                album => album,
+            }
+        }
+    };
+    let key = album.key(&session.server().name);
+
+    let (hash, mut original) = match cache.album(&key).await {
+        Some(Some(hash)) => (hash, None),
+        Some(None) => return Ok(placeholder()),
+        None => {
+            let Some(art) = fetch_album(&session, &album).await? else {
+                cache.set_album(&key, None).await;
+                return Ok(placeholder());
+            };
+
+            let hash = art::hash(&art);
+            let art: Arc<[u8]> = art.into();
+            cache
+                .set_picture(&Rendition::ORIGINAL.name(&hash), art.clone())
+                .await;
+            cache.set_album(&key, Some(&hash)).await;
+            (hash, Some(art))
+        }
+    };
+
+    let etag = EntityTag::new_strong(rendition.name(&hash));
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .unwrap_or_default();
+    if if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || tag.trim().parse().is_ok_and(|tag| etag.weak_eq(&tag)))
+    {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish());
     }
+
+    let data = match cache.picture(&rendition.name(&hash)).await {
+        Some(data) => data,
+        None => {
+            if original.is_none() {
+                original = cache.picture(&Rendition::ORIGINAL.name(&hash)).await;
+            }
+            // Evicted from memory without a cache directory to fall back on
+            let original = match original {
+                Some(original) => original,
+                None => match fetch_album(&session, &album).await? {
+                    Some(art) => art.into(),
+                    None => return Ok(placeholder()),
+                },
+            };
+
+            let source = original.clone();
+            match web::block(move || rendition.render(&source)).await {
+                Ok(Ok(data)) => {
+                    let data: Arc<[u8]> = data.into();
+                    cache
+                        .set_picture(&rendition.name(&hash), data.clone())
+                        .await;
+                    data
+                }
+                result => {
+                    if let Ok(Err(e)) = result {
+                        println!("Could not resize album art of {album:?} ({e})");
+                    }
+                    rendition = Rendition::ORIGINAL;
+                    original
+                }
+            }
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(rendition.mime_type(&data))
+        .insert_header(header::ETag(EntityTag::new_strong(rendition.name(&hash))))
+        .append_header(header::CacheControl(vec![CacheDirective::MaxAge(3600)]))
+        .body(data.to_vec()))
 }
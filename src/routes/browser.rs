@@ -1,4 +1,4 @@
-use crate::mpd;
+use crate::{mpd, routes::session::Session};
 use actix_web::{get, web, Responder};
 use askama::Template;
 use percent_encoding::percent_decode_str;
@@ -10,6 +10,9 @@ use std::path::Path;
 struct BrowserTemplate {
     path: Vec<String>,
     entries: Vec<mpd::Entry>,
+    play_next: bool,
+    stats: Option<mpd::Stats>,
+    permissions: mpd::Permissions,
 }
 
 #[derive(Deserialize, Default)]
@@ -19,16 +22,27 @@ struct BrowserQuery {
 }
 
 #[get("/browser")]
-pub async fn get_browser(query: web::Query<BrowserQuery>) -> impl Responder {
+pub async fn get_browser(
+    session: Session,
+    query: web::Query<BrowserQuery>,
+) -> mpd::Result<impl Responder> {
     let path = percent_decode_str(&query.path).decode_utf8_lossy();
-    let mut mpd = mpd::get_instance().await;
-    let entries = mpd.ls(&path).await.unwrap();
+    let mut mpd = session.mpd().await?;
+    let entries = mpd.ls(&path).await?;
+    let stats = if path.is_empty() {
+        Some(mpd.stats().await?)
+    } else {
+        None
+    };
 
-    BrowserTemplate {
+    Ok(BrowserTemplate {
         path: Path::new(&*path)
             .iter()
             .map(|s| s.to_string_lossy().to_string())
             .collect(),
         entries,
-    }
+        play_next: mpd.capabilities().supports(mpd::Feature::AddPosition),
+        stats,
+        permissions: session.permissions(&mpd),
+    })
 }
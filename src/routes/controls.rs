@@ -1,76 +1,183 @@
 use actix_web::{post, web, HttpResponse, Responder};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::mpd;
+use crate::{mpd, routes::session::Session};
 
-async fn toggle_setting(setting: &str) -> anyhow::Result<()> {
-    let mut mpd = mpd::get_instance().await;
+async fn toggle_setting(
+    session: &Session,
+    setting: &str,
+    get: impl FnOnce(&mpd::Status) -> bool,
+) -> mpd::Result<()> {
+    let mut mpd = session.mpd().await?;
 
-    let status = mpd.command("status").await?.into_hashmap();
-    let value = status[setting] == "1";
+    let value = get(&mpd.status().await?);
 
     mpd.command(&format!("{} {}", setting, if value { 0 } else { 1 }))
         .await?;
     Ok(())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct PostPlayQuery {
     #[serde(default)]
     position: Option<String>,
 }
 
+#[utoipa::path(tag = "controls", params(PostPlayQuery), responses((status = 204)))]
 #[post("/play")]
-pub async fn post_play(query: web::Query<PostPlayQuery>) -> impl Responder {
-    let mut mpd = mpd::get_instance().await;
-    mpd.play(query.position.as_deref()).await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_play(
+    session: Session,
+    query: web::Query<PostPlayQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.play(query.position.as_deref()).await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SeekQuery {
+    /// Seconds, relative to the current position if prefixed with `+` or `-`
+    time: String,
+    /// The queue item to seek in, instead of the current song
+    #[serde(default)]
+    id: Option<u32>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(super) enum SeekError {
+    #[error("invalid time")]
+    InvalidTime,
+    #[error("nothing is playing")]
+    NothingPlaying,
+    #[error("song is not seekable")]
+    NotSeekable,
+    #[error(transparent)]
+    Mpd(#[from] mpd::Error),
+}
+
+/// Seeks to `time` seconds, or by `time` seconds if `relative`, in the queue
+/// item `id` or the current song, staying within the song.
+pub(super) async fn seek(
+    mpd: &mut mpd::Mpd,
+    time: f64,
+    relative: bool,
+    id: Option<u32>,
+) -> Result<(), SeekError> {
+    if !time.is_finite() || (!relative && time < 0.0) {
+        return Err(SeekError::InvalidTime);
+    }
+
+    let status = mpd.status().await?;
+
+    let id = id.or(status.song_id);
+    let is_current = id.is_some() && id == status.song_id;
+    let Some(id) = id else {
+        return Err(SeekError::NothingPlaying);
+    };
+
+    let (elapsed, duration) = if is_current {
+        (status.elapsed.unwrap_or(0.0), status.duration)
+    } else {
+        let song = mpd
+            .command(&format!("playlistid {id}"))
+            .await?
+            .into_song()?;
+        (0.0, song.and_then(|song| song.duration))
+    };
+
+    let Some(duration) = duration else {
+        return Err(SeekError::NotSeekable);
+    };
+
+    let time = if relative { elapsed + time } else { time };
+    let time = time.clamp(0.0, duration);
+
+    if is_current {
+        mpd.command(&format!("seekcur {time:.3}")).await?;
+    } else {
+        mpd.command(&format!("seekid {id} {time:.3}")).await?;
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(tag = "controls", params(SeekQuery), responses((status = 204)))]
+#[post("/seek")]
+pub async fn post_seek(
+    session: Session,
+    query: web::Query<SeekQuery>,
+) -> mpd::Result<impl Responder> {
+    let relative = query.time.starts_with(['+', '-']);
+    let Ok(time) = query.time.parse() else {
+        return Ok(HttpResponse::BadRequest().body("invalid time"));
+    };
+
+    let mut mpd = session.mpd().await?;
+    match seek(&mut mpd, time, relative, query.id).await {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e @ SeekError::InvalidTime) => Ok(HttpResponse::BadRequest().body(e.to_string())),
+        Err(e @ (SeekError::NothingPlaying | SeekError::NotSeekable)) => {
+            Ok(HttpResponse::Conflict().body(e.to_string()))
+        }
+        Err(SeekError::Mpd(e)) => Err(e),
+    }
+}
+
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/pause")]
-pub async fn post_pause() -> impl Responder {
-    mpd::command("pause 1").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_pause(session: Session) -> mpd::Result<impl Responder> {
+    session.mpd().await?.command("pause 1").await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/previous")]
-pub async fn post_previous() -> impl Responder {
-    mpd::command("previous").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_previous(session: Session) -> mpd::Result<impl Responder> {
+    session.mpd().await?.command("previous").await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/next")]
-pub async fn post_next() -> impl Responder {
-    mpd::command("next").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_next(session: Session) -> mpd::Result<impl Responder> {
+    session.mpd().await?.command("next").await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/consume")]
-pub async fn post_consume() -> impl Responder {
-    toggle_setting("consume").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_consume(session: Session) -> mpd::Result<impl Responder> {
+    toggle_setting(&session, "consume", |s| s.consume.is_enabled()).await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/random")]
-pub async fn post_random() -> impl Responder {
-    toggle_setting("random").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_random(session: Session) -> mpd::Result<impl Responder> {
+    toggle_setting(&session, "random", |s| s.random).await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/repeat")]
-pub async fn post_repeat() -> impl Responder {
-    toggle_setting("repeat").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_repeat(session: Session) -> mpd::Result<impl Responder> {
+    toggle_setting(&session, "repeat", |s| s.repeat).await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/shuffle")]
-pub async fn post_shuffle() -> impl Responder {
-    mpd::command("shuffle").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_shuffle(session: Session) -> mpd::Result<impl Responder> {
+    session.mpd().await?.command("shuffle").await?;
+    Ok(HttpResponse::NoContent())
 }
 
+#[utoipa::path(tag = "controls", responses((status = 204)))]
 #[post("/single")]
-pub async fn post_single() -> impl Responder {
-    toggle_setting("single").await.unwrap();
-    HttpResponse::NoContent()
+pub async fn post_single(session: Session) -> mpd::Result<impl Responder> {
+    toggle_setting(&session, "single", |s| s.single.is_enabled()).await?;
+    Ok(HttpResponse::NoContent())
 }
@@ -1,19 +1,46 @@
-use crate::crate_version;
-use actix_web::{get, Responder};
+use crate::{auth, crate_version, mpd, routes::session::Session};
+use actix_web::{get, http::header, web, HttpResponse, Responder};
 use askama::Template;
-use serde::Deserialize;
 
 #[derive(Template)]
 #[template(path = "index.html")]
-struct IndexTemplate;
+struct IndexTemplate {
+    server: &'static mpd::Server,
+    servers: &'static [mpd::Server],
+    user: Option<auth::User>,
+    has_logout: bool,
+    permissions: mpd::Permissions,
+}
 
-#[derive(Deserialize, Default)]
-#[serde(default)]
-struct IndexQuery {
-    path: String,
+impl IndexTemplate {
+    fn is_current(&self, server: &mpd::Server) -> bool {
+        std::ptr::eq(self.server, server)
+    }
 }
 
+/// Redirects to the first configured server.
 #[get("/")]
-pub async fn get_index() -> impl Responder {
-    IndexTemplate
+pub async fn get_root() -> impl Responder {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, mpd::Server::all()[0].path()))
+        .finish()
+}
+
+#[get("/")]
+pub async fn get_index(session: Session, user: Option<web::ReqData<auth::User>>) -> impl Responder {
+    // If MPD is unreachable, the parts of the page loaded from it will say
+    // so, and the controls follow the user's role alone
+    let permissions = match session.mpd().await {
+        Ok(mpd) => session.permissions(&mpd),
+        Err(_) if user.as_ref().is_some_and(|user| user.is_guest()) => mpd::Permissions::READ,
+        Err(_) => mpd::Permissions::ALL,
+    };
+
+    IndexTemplate {
+        permissions,
+        user: user.map(web::ReqData::into_inner),
+        has_logout: auth::Auth::global().has_login(),
+        server: session.server(),
+        servers: mpd::Server::all(),
+    }
 }
@@ -0,0 +1,226 @@
+use crate::{mpd, routes::session::Session};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use askama::Template;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+struct Album {
+    name: String,
+    date: Option<String>,
+    /// A song from the album, to fetch the album art with
+    path: String,
+}
+
+enum LibraryView {
+    Artists(Vec<String>),
+    Artist(Vec<Album>),
+    Album(Vec<mpd::Entry>),
+    Genres(Vec<String>),
+    Genre(Vec<String>),
+}
+
+#[derive(Template)]
+#[template(path = "library.html")]
+struct LibraryTemplate {
+    query: LibraryQuery,
+    /// The query string selecting the songs in this view
+    params: String,
+    view: LibraryView,
+    play_next: bool,
+    permissions: mpd::Permissions,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+struct LibraryQuery {
+    artist: Option<String>,
+    album: Option<String>,
+    genre: Option<String>,
+    replace: bool,
+    next: bool,
+    play: bool,
+}
+
+impl LibraryQuery {
+    fn filter(&self, artist_tag: &str) -> Option<mpd::Filter> {
+        let filters = [
+            self.genre.as_ref().map(|g| mpd::Filter::equals("Genre", g)),
+            self.artist
+                .as_ref()
+                .map(|a| mpd::Filter::equals(artist_tag, a)),
+            self.album.as_ref().map(|a| mpd::Filter::equals("Album", a)),
+        ];
+        filters.into_iter().flatten().reduce(mpd::Filter::and)
+    }
+
+    fn params(&self) -> String {
+        [
+            ("artist", &self.artist),
+            ("album", &self.album),
+            ("genre", &self.genre),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = value.as_ref()?;
+            Some(format!(
+                "{key}={}",
+                utf8_percent_encode(value, NON_ALPHANUMERIC)
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+    }
+}
+
+fn albums(songs: Vec<mpd::Song>) -> Vec<Album> {
+    let mut albums: Vec<Album> = Vec::new();
+    for song in songs {
+        let Some(name) = song.album() else {
+            continue;
+        };
+
+        if !albums.iter().any(|album| album.name == name) {
+            albums.push(Album {
+                name: name.to_string(),
+                date: song.date().map(str::to_string),
+                path: song.file.clone(),
+            });
+        }
+    }
+
+    albums.sort_by(|a, b| (&a.date, &a.name).cmp(&(&b.date, &b.name)));
+    albums
+}
+
+impl LibraryTemplate {
+    fn new(session: &Session, mpd: &mpd::Mpd, query: LibraryQuery, view: LibraryView) -> Self {
+        Self {
+            params: query.params(),
+            query,
+            view,
+            play_next: mpd.capabilities().supports(mpd::Feature::AddPosition),
+            permissions: session.permissions(mpd),
+        }
+    }
+}
+
+#[get("/library/artists")]
+pub async fn get_artists(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let artists = mpd.list(tag, None).await?;
+
+    Ok(LibraryTemplate::new(
+        &session,
+        &mpd,
+        LibraryQuery::default(),
+        LibraryView::Artists(artists),
+    ))
+}
+
+#[get("/library/artist")]
+pub async fn get_artist(
+    session: Session,
+    query: web::Query<LibraryQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let songs = match query.filter(tag) {
+        Some(filter) => mpd.search(&filter, true, None).await?,
+        None => Vec::new(),
+    };
+
+    Ok(LibraryTemplate::new(
+        &session,
+        &mpd,
+        query.into_inner(),
+        LibraryView::Artist(albums(songs)),
+    ))
+}
+
+#[get("/library/album")]
+pub async fn get_album(
+    session: Session,
+    query: web::Query<LibraryQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let mut songs = match query.filter(tag) {
+        Some(filter) => mpd.search(&filter, true, None).await?,
+        None => Vec::new(),
+    };
+
+    songs.sort_by_key(|song| (song.disc(), song.track()));
+    let entries = songs.into_iter().map(mpd::Entry::from).collect();
+    Ok(LibraryTemplate::new(
+        &session,
+        &mpd,
+        query.into_inner(),
+        LibraryView::Album(entries),
+    ))
+}
+
+#[get("/library/genres")]
+pub async fn get_genres(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let genres = mpd.list("Genre", None).await?;
+
+    Ok(LibraryTemplate::new(
+        &session,
+        &mpd,
+        LibraryQuery::default(),
+        LibraryView::Genres(genres),
+    ))
+}
+
+#[get("/library/genre")]
+pub async fn get_genre(
+    session: Session,
+    query: web::Query<LibraryQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let artists = match query.filter(tag) {
+        Some(filter) => mpd.list(tag, Some(&filter)).await?,
+        None => Vec::new(),
+    };
+
+    Ok(LibraryTemplate::new(
+        &session,
+        &mpd,
+        query.into_inner(),
+        LibraryView::Genre(artists),
+    ))
+}
+
+#[utoipa::path(tag = "library", params(LibraryQuery), responses((status = 204)))]
+#[post("/library/queue")]
+pub async fn post_library_queue(
+    session: Session,
+    query: web::Query<LibraryQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let tag = mpd.capabilities().album_artist_tag();
+    let Some(filter) = query.filter(tag) else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+
+    let mut list = mpd.command_list();
+
+    if query.replace {
+        list.clear();
+    }
+
+    let position = if query.next { Some("+0") } else { None };
+    list.find_add(&filter, position)?;
+
+    if query.play {
+        list.play(None);
+    }
+
+    list.run().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,128 @@
+use crate::auth::Auth;
+use actix_web::{
+    get,
+    http::{header, StatusCode, Uri},
+    post, web, Either, HttpRequest, HttpResponse, Responder,
+};
+use askama::Template;
+use serde::Deserialize;
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate {
+    next: String,
+    failed: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LoginQuery {
+    next: String,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    name: String,
+    password: String,
+    #[serde(default)]
+    next: String,
+}
+
+/// Only allows going back to a page of Empede after logging in: a path with
+/// an optional query, and nothing browsers could read as another host, like
+/// `//host`, `/\host`, or a tab after the slash (which browsers strip).
+fn local_path(next: &str) -> &str {
+    let is_local = !next
+        .chars()
+        .any(|c| c.is_ascii_control() || c.is_whitespace() || c == '\\' || c == '#')
+        && next.parse::<Uri>().is_ok_and(|uri| {
+            uri.scheme().is_none()
+                && uri.authority().is_none()
+                && uri.path().starts_with('/')
+                && !uri.path().starts_with("//")
+        });
+
+    if is_local {
+        next
+    } else {
+        "/"
+    }
+}
+
+fn redirect(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+#[get("/login")]
+pub async fn get_login(query: web::Query<LoginQuery>) -> impl Responder {
+    if !Auth::global().has_login() {
+        return Either::Left(redirect("/"));
+    }
+
+    Either::Right(LoginTemplate {
+        next: local_path(&query.next).to_string(),
+        failed: false,
+    })
+}
+
+#[post("/login")]
+pub async fn post_login(form: web::Form<LoginForm>) -> impl Responder {
+    let form = form.into_inner();
+    let next = local_path(&form.next).to_string();
+
+    match Auth::global().login(form.name, form.password).await {
+        Some(cookie) => {
+            let mut response = redirect(&next);
+            let _ = response.add_cookie(&cookie);
+            Either::Left(response)
+        }
+        None => Either::Right(
+            LoginTemplate { next, failed: true }
+                .customize()
+                .with_status(StatusCode::UNAUTHORIZED),
+        ),
+    }
+}
+
+#[post("/logout")]
+pub async fn post_logout(req: HttpRequest) -> impl Responder {
+    let cookie = Auth::global().logout(&req);
+    let mut response = redirect("/login");
+    let _ = response.add_cookie(&cookie);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local_path;
+
+    #[test]
+    fn allows_local_paths() {
+        assert_eq!(local_path("/"), "/");
+        assert_eq!(local_path("/s/living-room/"), "/s/living-room/");
+        assert_eq!(
+            local_path("/s/default/?path=a%20b&x=1"),
+            "/s/default/?path=a%20b&x=1"
+        );
+    }
+
+    #[test]
+    fn rejects_other_hosts() {
+        for next in [
+            "",
+            "s/default/",
+            "//evil.com",
+            "/\\evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "/ /evil.com",
+            "https://evil.com/",
+            "javascript:alert(1)",
+            "/#fragment",
+        ] {
+            assert_eq!(local_path(next), "/", "{next:?}");
+        }
+    }
+}
@@ -1,7 +1,17 @@
+pub mod api;
 pub mod art;
 pub mod browser;
 pub mod controls;
 pub mod index;
+pub mod library;
+pub mod login;
+pub mod outputs;
+pub mod partitions;
 pub mod player;
+pub mod playlists;
 pub mod queue;
+pub mod search;
+pub mod session;
 pub mod sse;
+pub mod volume;
+pub mod ws;
@@ -0,0 +1,113 @@
+use crate::{mpd, routes::session::Session};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use askama::Template;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Template)]
+#[template(path = "outputs.html")]
+struct OutputsTemplate {
+    outputs: Vec<mpd::Output>,
+    output_set: bool,
+    /// Whether outputs can be moved into the selected partition
+    partitions: bool,
+    permissions: mpd::Permissions,
+}
+
+#[get("/outputs")]
+pub async fn get_outputs(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let outputs = mpd.outputs().await?;
+    let permissions = session.permissions(&mpd);
+
+    Ok(OutputsTemplate {
+        outputs,
+        output_set: permissions.admin && mpd.capabilities().supports(mpd::Feature::OutputSet),
+        partitions: permissions.admin && mpd.capabilities().supports(mpd::Feature::Partitions),
+        permissions,
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct OutputQuery {
+    id: u32,
+}
+
+#[utoipa::path(tag = "outputs", params(OutputQuery), responses((status = 204)))]
+#[post("/output/enable")]
+pub async fn post_output_enable(
+    session: Session,
+    query: web::Query<OutputQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.enable_output(query.id).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[utoipa::path(tag = "outputs", params(OutputQuery), responses((status = 204)))]
+#[post("/output/disable")]
+pub async fn post_output_disable(
+    session: Session,
+    query: web::Query<OutputQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.disable_output(query.id).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[utoipa::path(tag = "outputs", params(OutputQuery), responses((status = 204)))]
+#[post("/output/toggle")]
+pub async fn post_output_toggle(
+    session: Session,
+    query: web::Query<OutputQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.toggle_output(query.id).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct OutputAttributeForm {
+    name: String,
+    value: String,
+}
+
+#[utoipa::path(tag = "outputs", params(OutputQuery), responses((status = 204)))]
+#[post("/output/attribute")]
+pub async fn post_output_attribute(
+    session: Session,
+    query: web::Query<OutputQuery>,
+    form: web::Form<OutputAttributeForm>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    if !mpd.capabilities().supports(mpd::Feature::OutputSet) {
+        return Err(mpd::Error::Unsupported(mpd::Feature::OutputSet));
+    }
+
+    mpd.set_output_attribute(query.id, &form.name, &form.value)
+        .await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MoveOutputQuery {
+    name: String,
+}
+
+/// Moves an output into the partition this browser is bound to.
+#[utoipa::path(tag = "outputs", params(MoveOutputQuery), responses((status = 204)))]
+#[post("/output/move")]
+pub async fn post_output_move(
+    session: Session,
+    query: web::Query<MoveOutputQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    if !mpd.capabilities().supports(mpd::Feature::Partitions) {
+        return Err(mpd::Error::Unsupported(mpd::Feature::Partitions));
+    }
+
+    mpd.move_output(&query.name).await?;
+    Ok(HttpResponse::NoContent())
+}
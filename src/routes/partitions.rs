@@ -0,0 +1,119 @@
+use crate::{mpd, routes::session::Session};
+use std::time::Duration;
+
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use askama::Template;
+use serde::Deserialize;
+
+/// How often deleting a partition is tried while it still has clients.
+const DELETE_ATTEMPTS: u32 = 5;
+const DELETE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Template)]
+#[template(path = "partitions.html")]
+struct PartitionsTemplate {
+    partitions: Vec<String>,
+    current: String,
+    permissions: mpd::Permissions,
+}
+
+impl PartitionsTemplate {
+    fn is_current(&self, partition: &str) -> bool {
+        self.current == partition
+    }
+
+    fn is_default(&self, partition: &str) -> bool {
+        partition == mpd::DEFAULT_PARTITION
+    }
+}
+
+#[derive(Deserialize)]
+struct PartitionQuery {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct NameForm {
+    name: String,
+}
+
+#[get("/partitions")]
+pub async fn get_partitions(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let partitions = mpd.partitions().await?;
+    let current = mpd
+        .partition()
+        .unwrap_or(mpd::DEFAULT_PARTITION)
+        .to_string();
+
+    Ok(PartitionsTemplate {
+        permissions: session.permissions(&mpd),
+        partitions,
+        current,
+    })
+}
+
+#[post("/partitions")]
+pub async fn post_partitions(
+    session: Session,
+    form: web::Form<NameForm>,
+) -> mpd::Result<impl Responder> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let mut mpd = session.mpd().await?;
+    mpd.new_partition(name).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/partition")]
+pub async fn delete_partition(
+    session: Session,
+    query: web::Query<PartitionQuery>,
+) -> mpd::Result<impl Responder> {
+    session.server().close_partition(&query.name);
+
+    // Connections outside the pool take a moment to leave the partition
+    let mut mpd = session.mpd().await?;
+    let mut attempt = 1;
+    loop {
+        match mpd.delete_partition(&query.name).await {
+            Err(mpd::Error::Ack {
+                code: mpd::AckCode::Unknown,
+                message,
+                ..
+            }) => {
+                if attempt == DELETE_ATTEMPTS {
+                    return Ok(HttpResponse::Conflict()
+                        .body(format!("Could not delete the partition ({message})")));
+                }
+                tokio::time::sleep(DELETE_RETRY_DELAY).await;
+                attempt += 1;
+            }
+            result => {
+                result?;
+                return Ok(HttpResponse::NoContent().finish());
+            }
+        }
+    }
+}
+
+/// Binds this browser to a partition. The whole page is reloaded, so the
+/// SSE connection is bound to the new partition too.
+#[post("/partition/select")]
+pub async fn post_partition_select(
+    session: Session,
+    query: web::Query<PartitionQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    if !mpd.partitions().await?.contains(&query.name) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Ok(HttpResponse::NoContent()
+        .cookie(session.partition_cookie(&query.name))
+        .insert_header(("HX-Refresh", "true"))
+        .finish())
+}
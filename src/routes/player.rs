@@ -1,57 +1,60 @@
-use crate::mpd;
+use crate::{mpd, routes::session::Session};
 use actix_web::{get, Responder};
 use askama::Template;
-use std::collections::HashMap;
 
 #[derive(Template)]
 #[template(path = "player.html")]
 struct PlayerTemplate {
-    song: Option<HashMap<String, String>>,
+    song: Option<mpd::Song>,
     name: Option<String>,
-    state: String,
+    state: mpd::PlayerState,
     consume: bool,
     random: bool,
     repeat: bool,
     single: bool,
-    elapsed: f32,
-    duration: f32,
+    elapsed: f64,
+    duration: f64,
+    seekable: bool,
+    permissions: mpd::Permissions,
 }
 
-#[get("/player")]
-pub async fn get_player() -> impl Responder {
-    let mut mpd = mpd::get_instance().await;
-    let song = mpd.command("currentsong").await.unwrap().into_hashmap();
-    let status = mpd.command("status").await.unwrap().into_hashmap();
+mod filters {
+    /// Formats seconds as `m:ss`.
+    pub fn time(seconds: &f64) -> askama::Result<String> {
+        let seconds = seconds.max(0.0) as u64;
+        Ok(format!("{}:{:02}", seconds / 60, seconds % 60))
+    }
 
-    let elapsed = status
-        .get("elapsed")
-        .and_then(|e| e.parse().ok())
-        .unwrap_or(0.0);
-    let duration = status
-        .get("duration")
-        .and_then(|e| e.parse().ok())
-        .unwrap_or(1.0);
+    pub fn round(value: &f64) -> askama::Result<String> {
+        Ok(format!("{}", value.round()))
+    }
+}
 
-    let mut template = PlayerTemplate {
-        song: if song.is_empty() {
-            None
-        } else {
-            Some(song.clone())
-        },
-        name: None,
-        state: status["state"].clone(),
-        consume: status["consume"] == "1",
-        random: status["random"] == "1",
-        repeat: status["repeat"] == "1",
-        single: status["single"] == "1",
-        elapsed,
-        duration,
-    };
+#[get("/player")]
+pub async fn get_player(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let mut list = mpd.command_list();
+    list.command("currentsong").command("status");
+    let [song, status] = list
+        .run()
+        .await?
+        .try_into()
+        .expect("command list returns one result per command");
 
-    if !song.is_empty() {
-        let name = song.get("Title").unwrap_or(&song["file"]).to_string();
-        template.name = Some(name);
-    }
+    let song = song.into_song()?;
+    let status = status.into_status()?;
 
-    template
+    Ok(PlayerTemplate {
+        permissions: session.permissions(&mpd),
+        seekable: song.is_some() && status.duration.is_some(),
+        name: song.as_ref().map(mpd::Song::display_title),
+        song,
+        state: status.state,
+        consume: status.consume.is_enabled(),
+        random: status.random,
+        repeat: status.repeat,
+        single: status.single.is_enabled(),
+        elapsed: status.elapsed.unwrap_or(0.0),
+        duration: status.duration.unwrap_or(1.0),
+    })
 }
@@ -0,0 +1,203 @@
+use crate::{mpd, routes::session::Session};
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use askama::Template;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+enum PlaylistsView {
+    Playlists(Vec<mpd::StoredPlaylist>),
+    Playlist(String, Vec<mpd::Song>),
+}
+
+#[derive(Template)]
+#[template(path = "playlists.html")]
+struct PlaylistsTemplate {
+    view: PlaylistsView,
+    play_next: bool,
+    permissions: mpd::Permissions,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PlaylistQuery {
+    name: String,
+}
+
+/// Names entered by the user are sent as form data rather than in the query.
+#[derive(Deserialize, ToSchema)]
+struct NameForm {
+    name: String,
+}
+
+impl NameForm {
+    fn name(&self) -> Option<&str> {
+        let name = self.name.trim();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+#[get("/playlists")]
+pub async fn get_playlists(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let playlists = mpd.playlists().await?;
+
+    Ok(PlaylistsTemplate {
+        view: PlaylistsView::Playlists(playlists),
+        play_next: mpd.capabilities().supports(mpd::Feature::AddPosition),
+        permissions: session.permissions(&mpd),
+    })
+}
+
+#[utoipa::path(tag = "playlists", responses((status = 204)))]
+#[post("/playlists")]
+pub async fn post_playlists(
+    session: Session,
+    form: web::Form<NameForm>,
+) -> mpd::Result<impl Responder> {
+    let Some(name) = form.name() else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+
+    let mut mpd = session.mpd().await?;
+    mpd.save_playlist(name).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/playlist")]
+pub async fn get_playlist(
+    session: Session,
+    query: web::Query<PlaylistQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let songs = mpd.playlist_songs(&query.name).await?;
+
+    Ok(PlaylistsTemplate {
+        view: PlaylistsView::Playlist(query.into_inner().name, songs),
+        play_next: mpd.capabilities().supports(mpd::Feature::AddPosition),
+        permissions: session.permissions(&mpd),
+    })
+}
+
+#[utoipa::path(tag = "playlists", params(PlaylistQuery), responses((status = 204)))]
+#[delete("/playlist")]
+pub async fn delete_playlist(
+    session: Session,
+    query: web::Query<PlaylistQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.remove_playlist(&query.name).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[utoipa::path(tag = "playlists", params(PlaylistQuery), responses((status = 204)))]
+#[post("/playlist/rename")]
+pub async fn post_playlist_rename(
+    session: Session,
+    query: web::Query<PlaylistQuery>,
+    form: web::Form<NameForm>,
+) -> mpd::Result<impl Responder> {
+    let Some(new_name) = form.name() else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+
+    let mut mpd = session.mpd().await?;
+    mpd.rename_playlist(&query.name, new_name).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PlaylistAddQuery {
+    path: String,
+}
+
+#[utoipa::path(tag = "playlists", params(PlaylistAddQuery), responses((status = 204)))]
+#[post("/playlist/add")]
+pub async fn post_playlist_add(
+    session: Session,
+    query: web::Query<PlaylistAddQuery>,
+    form: web::Form<NameForm>,
+) -> mpd::Result<impl Responder> {
+    let Some(name) = form.name() else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+
+    let path = percent_decode_str(&query.path).decode_utf8_lossy();
+    let mut mpd = session.mpd().await?;
+    mpd.playlist_add(name, &path).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeletePlaylistSongQuery {
+    name: String,
+    position: u32,
+}
+
+#[utoipa::path(tag = "playlists", params(DeletePlaylistSongQuery), responses((status = 204)))]
+#[delete("/playlist/song")]
+pub async fn delete_playlist_song(
+    session: Session,
+    query: web::Query<DeletePlaylistSongQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.playlist_delete(&query.name, query.position).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MovePlaylistSongBody {
+    from: u32,
+    to: u32,
+}
+
+#[utoipa::path(tag = "playlists", params(PlaylistQuery), responses((status = 204)))]
+#[post("/playlist/move")]
+pub async fn post_playlist_move(
+    session: Session,
+    query: web::Query<PlaylistQuery>,
+    body: web::Json<MovePlaylistSongBody>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.playlist_move(&query.name, body.from, body.to).await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LoadPlaylistQuery {
+    name: String,
+    #[serde(default)]
+    replace: bool,
+    #[serde(default)]
+    next: bool,
+    #[serde(default)]
+    play: bool,
+}
+
+#[utoipa::path(tag = "playlists", params(LoadPlaylistQuery), responses((status = 204)))]
+#[post("/playlist/load")]
+pub async fn post_playlist_load(
+    session: Session,
+    query: web::Query<LoadPlaylistQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let mut list = mpd.command_list();
+
+    if query.replace {
+        list.clear();
+    }
+
+    let position = if query.next { Some("+0") } else { None };
+    list.load(&query.name, position)?;
+
+    if query.play {
+        list.play(None);
+    }
+
+    list.run().await?;
+
+    Ok(HttpResponse::NoContent())
+}
@@ -1,23 +1,216 @@
-use crate::mpd;
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use std::ops::Range;
+
+use crate::{mpd, routes::session::Session};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
 use askama::Template;
 use percent_encoding::percent_decode_str;
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// How many rows are rendered around the current song, and at most loaded
+/// at once while scrolling.
+const PAGE_SIZE: u32 = 100;
+
+/// Rows yet to be loaded, which a placeholder row requests once it is
+/// scrolled into view.
+struct Page {
+    offset: u32,
+    limit: u32,
+    before: bool,
+}
+
+impl Page {
+    /// The page before rows starting at `start`, if there are any.
+    fn before(start: u32) -> Option<Self> {
+        let offset = start.saturating_sub(PAGE_SIZE);
+        (start > 0).then_some(Self {
+            offset,
+            limit: start - offset,
+            before: true,
+        })
+    }
+
+    /// The page after rows ending at `end`, if there are any.
+    fn after(end: u32, length: u32) -> Option<Self> {
+        (end < length).then_some(Self {
+            offset: end,
+            limit: PAGE_SIZE,
+            before: false,
+        })
+    }
+}
 
 #[derive(Template)]
 #[template(path = "queue.html")]
 struct QueueTemplate {
-    queue: Vec<mpd::QueueItem>,
+    version: u32,
+    length: u32,
+    current: Option<u32>,
+    items: Vec<mpd::QueueItem>,
+    before: Option<Page>,
+    after: Option<Page>,
+    permissions: mpd::Permissions,
 }
 
-#[get("/queue")]
-pub async fn get_queue() -> impl Responder {
-    let mut mpd = mpd::get_instance().await;
-    let queue = mpd.playlist().await.unwrap();
-    QueueTemplate { queue }
+#[derive(Template)]
+#[template(path = "queue_page.html")]
+struct QueuePageTemplate {
+    items: Vec<mpd::QueueItem>,
+    before: Option<Page>,
+    after: Option<Page>,
+    permissions: mpd::Permissions,
+}
+
+/// Out-of-band swaps for the rows that changed since the client's version.
+#[derive(Template)]
+#[template(path = "queue_update.html")]
+struct QueueUpdateTemplate {
+    version: u32,
+    length: u32,
+    current: Option<u32>,
+    replaced: Vec<mpd::QueueItem>,
+    appended: Vec<mpd::QueueItem>,
+    removed: Range<u32>,
+    after: Option<Page>,
+    permissions: mpd::Permissions,
 }
 
 #[derive(Deserialize)]
+struct QueueQuery {
+    /// A page of rows to load, preceding already rendered rows if `before`
+    #[serde(default)]
+    offset: Option<u32>,
+    #[serde(default)]
+    limit: Option<u32>,
+    #[serde(default)]
+    before: bool,
+    /// The state of the queue the client has rendered, if any, with
+    /// `start..end` being the positions of its rows
+    #[serde(default)]
+    version: Option<u32>,
+    #[serde(default)]
+    length: Option<u32>,
+    #[serde(default)]
+    current: Option<u32>,
+    #[serde(default)]
+    start: Option<u32>,
+    #[serde(default)]
+    end: Option<u32>,
+}
+
+impl QueueQuery {
+    /// The rows of the requested page, which is at most [`PAGE_SIZE`] long.
+    fn page(&self) -> Option<Range<u32>> {
+        let offset = self.offset?;
+        let limit = self.limit.unwrap_or(PAGE_SIZE).clamp(1, PAGE_SIZE);
+        Some(offset..offset.saturating_add(limit))
+    }
+}
+
+/// The rows to look for changes in, for a client that has rendered rows
+/// `start..end` of a queue that was `length` rows long, or `None` if those
+/// rows were never in it. Rows are only appended when the client has
+/// rendered up to the end.
+fn changed_rows(start: u32, end: u32, length: u32) -> Option<Range<u32>> {
+    if start > end || end > length {
+        None
+    } else if end == length {
+        Some(start..end.saturating_add(PAGE_SIZE))
+    } else {
+        Some(start..end)
+    }
+}
+
+/// Renders the rows around the current song.
+async fn queue_window(
+    mpd: &mut mpd::Mpd,
+    permissions: mpd::Permissions,
+) -> mpd::Result<QueueTemplate> {
+    let current = mpd.status().await?.song.unwrap_or(0);
+    let start = current.saturating_sub(PAGE_SIZE / 2);
+    let queue = mpd
+        .playlist(None, start..start.saturating_add(PAGE_SIZE))
+        .await?;
+
+    Ok(QueueTemplate {
+        version: queue.version,
+        length: queue.length,
+        current: queue.current,
+        items: queue.items,
+        before: Page::before(start),
+        after: Page::after(start.saturating_add(PAGE_SIZE), queue.length),
+        permissions,
+    })
+}
+
+#[get("/queue")]
+pub async fn get_queue(
+    req: HttpRequest,
+    session: Session,
+    query: web::Query<QueueQuery>,
+) -> mpd::Result<HttpResponse> {
+    let mut mpd = session.mpd().await?;
+    let permissions = session.permissions(&mpd);
+
+    if let Some(page) = query.page() {
+        let queue = mpd.playlist(None, page.clone()).await?;
+        let template = QueuePageTemplate {
+            items: queue.items,
+            before: Page::before(page.start).filter(|_| query.before),
+            after: Page::after(page.end, queue.length).filter(|_| !query.before),
+            permissions,
+        };
+        return Ok(template.respond_to(&req));
+    }
+
+    let (Some(version), Some(length), Some(start), Some(end)) =
+        (query.version, query.length, query.start, query.end)
+    else {
+        return Ok(queue_window(&mut mpd, permissions).await?.respond_to(&req));
+    };
+    let Some(range) = changed_rows(start, end, length) else {
+        return Ok(HttpResponse::BadRequest().body("invalid range of rows"));
+    };
+    let queue = mpd.playlist(Some(version), range.clone()).await?;
+    if start > 0 && start >= queue.length {
+        // None of the rendered rows are left
+        return Ok(queue_window(&mut mpd, permissions).await?.respond_to(&req));
+    }
+
+    let mut items = queue.items;
+
+    // The current song moving only changes which rows are highlighted
+    if query.current != queue.current {
+        let positions: Vec<_> = [query.current, queue.current]
+            .into_iter()
+            .flatten()
+            .filter(|position| (start..end.min(queue.length)).contains(position))
+            .filter(|&position| !items.iter().any(|item| item.position == position))
+            .collect();
+        items.extend(mpd.queue_items(&positions, queue.current).await?);
+    }
+
+    let (replaced, appended) = items.into_iter().partition(|item| item.position < end);
+    let template = QueueUpdateTemplate {
+        version: queue.version,
+        length: queue.length,
+        current: queue.current,
+        replaced,
+        appended,
+        removed: queue.length.max(start)..end,
+        after: Page::after(range.end, queue.length).filter(|_| end >= length),
+        permissions,
+    };
+
+    Ok(template
+        .customize()
+        .insert_header(("HX-Reswap", "none"))
+        .respond_to(&req)
+        .map_into_boxed_body())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct PostQueueQuery {
     path: String,
     #[serde(default)]
@@ -28,57 +221,164 @@ struct PostQueueQuery {
     play: bool,
 }
 
+#[utoipa::path(tag = "queue", params(PostQueueQuery), responses((status = 204)))]
 #[post("/queue")]
-pub async fn post_queue(query: web::Query<PostQueueQuery>) -> impl Responder {
+pub async fn post_queue(
+    session: Session,
+    query: web::Query<PostQueueQuery>,
+) -> mpd::Result<impl Responder> {
     let path = percent_decode_str(&query.path).decode_utf8_lossy();
-    let mut mpd = mpd::get_instance().await;
+    let mut mpd = session.mpd().await?;
+    let mut list = mpd.command_list();
 
     if query.replace {
-        mpd.clear().await.unwrap();
+        list.clear();
     }
 
     if query.next {
-        mpd.add_position(&path, "+0").await.unwrap();
+        list.add_position(&path, "+0")?;
     } else {
-        mpd.add(&path).await.unwrap();
+        list.add(&path);
     }
 
     if query.play {
-        mpd.play(None).await.unwrap();
+        list.play(None);
     }
 
-    HttpResponse::NoContent()
+    list.run().await?;
+
+    Ok(HttpResponse::NoContent())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct DeleteQueueQuery {
     #[serde(default)]
     id: Option<u32>,
 }
 
+#[utoipa::path(tag = "queue", params(DeleteQueueQuery), responses((status = 204)))]
 #[delete("/queue")]
-pub async fn delete_queue(query: web::Query<DeleteQueueQuery>) -> impl Responder {
-    let mut mpd = mpd::get_instance().await;
+pub async fn delete_queue(
+    session: Session,
+    query: web::Query<DeleteQueueQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
     if let Some(id) = query.id {
-        mpd.command(&format!("deleteid {id}")).await.unwrap();
+        mpd.command(&format!("deleteid {id}")).await?;
     } else {
-        mpd.command("clear").await.unwrap();
+        mpd.command("clear").await?;
     }
 
-    HttpResponse::NoContent()
+    Ok(HttpResponse::NoContent())
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 struct UpdateQueueBody {
     from: u32,
     to: u32,
 }
 
+#[utoipa::path(tag = "queue", responses((status = 204)))]
 #[post("/queue/move")]
-pub async fn post_queue_move(body: web::Json<UpdateQueueBody>) -> impl Responder {
-    let mut mpd = mpd::get_instance().await;
+pub async fn post_queue_move(
+    session: Session,
+    body: web::Json<UpdateQueueBody>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
     mpd.command(&format!("move {} {}", body.from, body.to))
-        .await
-        .unwrap();
-    HttpResponse::NoContent()
+        .await?;
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SendQueueForm {
+    to: String,
+}
+
+/// Replaces the queue of another server with this one, continuing playback
+/// there at the same position. Both servers need to share the same library.
+#[utoipa::path(tag = "queue", responses((status = 204)))]
+#[post("/queue/send")]
+pub async fn post_queue_send(
+    session: Session,
+    form: web::Form<SendQueueForm>,
+) -> mpd::Result<impl Responder> {
+    let Some(target) = mpd::Server::get(&form.to) else {
+        return Ok(HttpResponse::NotFound().body("unknown server"));
+    };
+    if std::ptr::eq(target, session.server()) {
+        return Ok(HttpResponse::BadRequest().body("cannot send the queue to the same server"));
+    }
+
+    let mut mpd = session.mpd().await?;
+    let mut list = mpd.command_list();
+    list.command("status").command("playlistinfo");
+    let [status, songs] = list
+        .run()
+        .await?
+        .try_into()
+        .expect("command list returns one result per command");
+    let status = status.into_status()?;
+    let songs = songs.into_songs()?;
+    drop(mpd);
+
+    let mut target = target.get_instance(None, session.password()).await?;
+    let mut list = target.command_list();
+    list.clear();
+    for song in &songs {
+        list.add(&song.file);
+    }
+
+    if let (mpd::PlayerState::Play, Some(position)) = (status.state, status.song) {
+        let elapsed = status.elapsed.unwrap_or(0.0);
+        list.command(format!("seek {position} {elapsed:.3}"));
+    }
+
+    list.run().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(query: &str) -> Option<Range<u32>> {
+        web::Query::<QueueQuery>::from_query(query).unwrap().page()
+    }
+
+    #[test]
+    fn limits_pages() {
+        assert_eq!(page(""), None);
+        assert_eq!(page("offset=10"), Some(10..110));
+        assert_eq!(page("offset=10&limit=5"), Some(10..15));
+        assert_eq!(page("offset=10&limit=0"), Some(10..11));
+        assert_eq!(page("offset=10&limit=1000"), Some(10..110));
+        assert_eq!(page("offset=4294967290"), Some(4294967290..u32::MAX));
+    }
+
+    #[test]
+    fn pages_around_rendered_rows() {
+        assert!(Page::before(0).is_none());
+        let before = Page::before(30).unwrap();
+        assert_eq!((before.offset, before.limit, before.before), (0, 30, true));
+        let before = Page::before(250).unwrap();
+        assert_eq!((before.offset, before.limit), (150, 100));
+
+        assert!(Page::after(50, 50).is_none());
+        assert!(Page::after(60, 50).is_none());
+        let after = Page::after(50, 51).unwrap();
+        assert_eq!((after.offset, after.limit, after.before), (50, 100, false));
+    }
+
+    #[test]
+    fn looks_for_changes_in_rendered_rows() {
+        assert_eq!(changed_rows(10, 20, 30), Some(10..20));
+        assert_eq!(changed_rows(10, 30, 30), Some(10..130));
+        assert_eq!(changed_rows(0, 0, 0), Some(0..100));
+        assert_eq!(changed_rows(0, u32::MAX, u32::MAX), Some(0..u32::MAX));
+        assert_eq!(changed_rows(20, 10, 30), None);
+        assert_eq!(changed_rows(10, 40, 30), None);
+    }
 }
@@ -0,0 +1,130 @@
+use crate::{mpd, routes::session::Session};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use askama::Template;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum SearchTag {
+    #[default]
+    Any,
+    Artist,
+    Album,
+    Title,
+    Genre,
+}
+
+impl SearchTag {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Title => "title",
+            Self::Genre => "genre",
+        }
+    }
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub(super) struct SearchQuery {
+    q: String,
+    tag: SearchTag,
+    exact: bool,
+    pub(super) page: usize,
+}
+
+impl SearchQuery {
+    fn filter(&self) -> mpd::Filter {
+        if self.exact {
+            mpd::Filter::equals(self.tag.as_str(), &self.q)
+        } else {
+            mpd::Filter::contains(self.tag.as_str(), &self.q)
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    q: String,
+    tag: &'static str,
+    exact: bool,
+    /// The query string for the current search, without the page
+    params: String,
+    page: usize,
+    has_next: bool,
+    entries: Vec<mpd::Entry>,
+    queue_all: bool,
+    permissions: mpd::Permissions,
+}
+
+/// Returns the requested page of results, and whether there is a next page.
+pub(super) async fn search_page(
+    mpd: &mut mpd::Mpd,
+    query: &SearchQuery,
+) -> mpd::Result<(Vec<mpd::Song>, bool)> {
+    if query.q.is_empty() {
+        return Ok((Vec::new(), false));
+    }
+
+    let start = query.page * PAGE_SIZE;
+    // Fetch one extra song to find out whether there is a next page
+    let window = start..start + PAGE_SIZE + 1;
+    let mut songs = mpd
+        .search(&query.filter(), query.exact, Some(window))
+        .await?;
+
+    let has_next = songs.len() > PAGE_SIZE;
+    songs.truncate(PAGE_SIZE);
+    Ok((songs, has_next))
+}
+
+#[get("/search")]
+pub async fn get_search(
+    session: Session,
+    query: web::Query<SearchQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let (songs, has_next) = search_page(&mut mpd, &query).await?;
+
+    let feature = if query.exact {
+        mpd::Feature::FindAdd
+    } else {
+        mpd::Feature::SearchAdd
+    };
+
+    Ok(SearchTemplate {
+        q: query.q.clone(),
+        tag: query.tag.as_str(),
+        exact: query.exact,
+        params: format!(
+            "q={}&tag={}&exact={}",
+            utf8_percent_encode(&query.q, NON_ALPHANUMERIC),
+            query.tag.as_str(),
+            query.exact
+        ),
+        page: query.page,
+        has_next,
+        entries: songs.into_iter().map(mpd::Entry::from).collect(),
+        queue_all: mpd.capabilities().supports(feature),
+        permissions: session.permissions(&mpd),
+    })
+}
+
+#[utoipa::path(tag = "library", params(SearchQuery), responses((status = 204)))]
+#[post("/search/queue")]
+pub async fn post_search_queue(
+    session: Session,
+    query: web::Query<SearchQuery>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    mpd.search_add(&query.filter(), query.exact).await?;
+    Ok(HttpResponse::NoContent())
+}
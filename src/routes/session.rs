@@ -0,0 +1,119 @@
+use std::future::{ready, Ready};
+
+use actix_web::{cookie::Cookie, dev::Payload, error, FromRequest, HttpMessage, HttpRequest};
+
+use crate::{auth, mpd};
+
+const PARTITION_COOKIE: &str = "partition";
+
+/// What a browser has selected, which every request to MPD is made on
+/// behalf of: the server from the `/s/{server}` path (the first server if
+/// there is none), and the partition from a cookie scoped to that path, so it
+/// survives reloads and is sent along with the SSE connection. Connections
+/// use the MPD password of the logged in user, if they have one.
+#[derive(Clone)]
+pub struct Session {
+    server: &'static mpd::Server,
+    partition: Option<String>,
+    user: Option<auth::User>,
+}
+
+impl Session {
+    pub fn server(&self) -> &'static mpd::Server {
+        self.server
+    }
+
+    /// The selected partition, `None` being the default one.
+    pub fn partition(&self) -> Option<&str> {
+        self.partition.as_deref()
+    }
+
+    /// The user's MPD password, `None` meaning the server's password.
+    pub fn password(&self) -> Option<&str> {
+        self.user.as_ref()?.mpd_password.as_deref()
+    }
+
+    /// What the user may do on a connection: whatever its password allows,
+    /// except for guests, who may only look.
+    pub fn permissions(&self, mpd: &mpd::Mpd) -> mpd::Permissions {
+        match &self.user {
+            Some(user) if user.is_guest() => mpd::Permissions::READ,
+            _ => mpd.capabilities().permissions(),
+        }
+    }
+
+    /// Returns a connection bound to the selected partition. If that
+    /// partition no longer exists, the default partition is used instead.
+    pub async fn mpd(&self) -> mpd::Result<mpd::PooledMpd> {
+        match self
+            .server
+            .get_instance(self.partition(), self.password())
+            .await
+        {
+            Err(mpd::Error::Ack {
+                code: mpd::AckCode::NoExist,
+                ..
+            }) => self.server.get_instance(None, self.password()).await,
+            result => result,
+        }
+    }
+
+    /// A fresh connection outside the pool, e.g. for `idle`, bound to the
+    /// selected partition.
+    pub async fn connect(&self) -> mpd::Result<mpd::Mpd> {
+        let mut info = self.server.info.clone();
+        if let Some(password) = self.password() {
+            info.password = Some(password.to_string());
+        }
+        let mut mpd = mpd::Mpd::new(info);
+        mpd.connect().await?;
+        if let Err(e) = mpd.switch_partition(self.partition()).await {
+            if !matches!(
+                e,
+                mpd::Error::Ack {
+                    code: mpd::AckCode::NoExist,
+                    ..
+                }
+            ) {
+                return Err(e);
+            }
+        }
+        Ok(mpd)
+    }
+
+    pub fn partition_cookie(&self, partition: &str) -> Cookie<'static> {
+        Cookie::build(PARTITION_COOKIE, partition.to_string())
+            .path(self.server.path())
+            .permanent()
+            .finish()
+    }
+}
+
+impl FromRequest for Session {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, actix_web::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Routes outside of a `/s/{server}` scope use the first server
+        let server = match req.match_info().get("server") {
+            Some(name) => mpd::Server::get(name),
+            None => mpd::Server::all().first(),
+        };
+        let Some(server) = server else {
+            return ready(Err(error::ErrorNotFound("unknown server")));
+        };
+
+        let partition = req
+            .cookie(PARTITION_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+            .filter(|partition| !partition.is_empty() && partition != mpd::DEFAULT_PARTITION);
+
+        let user = req.extensions().get::<auth::User>().cloned();
+
+        ready(Ok(Self {
+            server,
+            partition,
+            user,
+        }))
+    }
+}
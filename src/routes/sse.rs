@@ -3,31 +3,74 @@ use std::time::Duration;
 use actix_web::{get, Responder};
 use actix_web_lab::sse;
 
-use crate::mpd::Mpd;
+use crate::{mpd, routes::session::Session};
 
 #[get("/idle")]
-pub async fn idle() -> impl Responder {
-    let mut mpd = Mpd::new();
-    mpd.connect().await.unwrap();
+pub async fn idle(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.connect().await?;
 
-    const SYSTEMS: &[&str] = &["playlist", "player", "database", "options"];
+    const SYSTEMS: &[&str] = &[
+        "playlist",
+        "player",
+        "database",
+        "options",
+        "stored_playlist",
+        "mixer",
+        "output",
+    ];
+
+    // Older servers reject `idle` for subsystems they don't know about
+    let mut subscribed = SYSTEMS.to_vec();
+    if mpd.capabilities().supports(mpd::Feature::Partitions) {
+        subscribed.push("partition");
+    }
 
     let (tx, rx) = tokio::sync::mpsc::channel(10);
-    for system in SYSTEMS {
+    for system in &subscribed {
         _ = tx
             .send(sse::Data::new("").event(system.to_owned()).into())
             .await;
     }
 
+    let server = session.server();
     actix_web::rt::spawn(async move {
         loop {
-            let systems = mpd.idle(SYSTEMS).await.unwrap();
+            let deleted = server.partition_deleted(mpd.partition());
+            let result = tokio::select! {
+                result = mpd.idle(&subscribed) => result,
+                () = deleted => match mpd.leave_partition().await {
+                    // Everything differs in the default partition
+                    Ok(()) => Ok(subscribed.iter().map(|s| s.to_string()).collect()),
+                    Err(_) => break,
+                },
+            };
+
+            let systems = match result {
+                Ok(systems) => systems,
+                Err(e) => {
+                    println!("Lost idle connection to MPD ({e}), reconnecting");
+                    if mpd.reconnect().await.is_err() {
+                        // Dropping the sender closes the stream, making the client reconnect
+                        break;
+                    }
+
+                    // Anything could have changed while we were disconnected
+                    subscribed.iter().map(|s| s.to_string()).collect()
+                }
+            };
 
             for system in systems {
-                _ = tx.send(sse::Data::new("").event(system).into()).await;
+                if tx
+                    .send(sse::Data::new("").event(system).into())
+                    .await
+                    .is_err()
+                {
+                    // The client went away
+                    return;
+                }
             }
         }
     });
 
-    sse::Sse::from_infallible_receiver(rx).with_retry_duration(Duration::from_secs(10))
+    Ok(sse::Sse::from_infallible_receiver(rx).with_retry_duration(Duration::from_secs(10)))
 }
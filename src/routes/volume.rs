@@ -0,0 +1,84 @@
+use crate::{mpd, routes::session::Session};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use askama::Template;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Template)]
+#[template(path = "volume.html")]
+struct VolumeTemplate {
+    volume: Option<u32>,
+    permissions: mpd::Permissions,
+}
+
+impl VolumeTemplate {
+    fn muted(&self) -> bool {
+        self.volume == Some(0)
+    }
+
+    fn icon(&self) -> &'static str {
+        match self.volume {
+            Some(0) => "volume_off",
+            Some(volume) if volume < 50 => "volume_down",
+            _ => "volume_up",
+        }
+    }
+}
+
+#[get("/volume")]
+pub async fn get_volume(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let volume = mpd.volume().await?;
+    Ok(VolumeTemplate {
+        volume,
+        permissions: session.permissions(&mpd),
+    })
+}
+
+/// Either an absolute `value` from 0 to 100, or a relative `change`.
+#[derive(Deserialize, ToSchema)]
+struct VolumeForm {
+    #[serde(default)]
+    value: Option<u32>,
+    #[serde(default)]
+    change: Option<i32>,
+}
+
+#[utoipa::path(tag = "volume", responses((status = 204)))]
+#[post("/volume")]
+pub async fn post_volume(
+    session: Session,
+    form: web::Form<VolumeForm>,
+) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    match (form.value, form.change) {
+        (Some(value), _) => mpd.set_volume(value).await?,
+        (None, Some(change)) => mpd.change_volume(change).await?,
+        (None, None) => return Ok(HttpResponse::BadRequest().finish()),
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(tag = "volume", responses((status = 204)))]
+#[post("/volume/mute")]
+pub async fn post_volume_mute(session: Session) -> mpd::Result<impl Responder> {
+    let mut mpd = session.mpd().await?;
+    let Some(volume) = mpd.volume().await? else {
+        return Ok(HttpResponse::Conflict().finish());
+    };
+
+    let server = session.server();
+    let partition = mpd
+        .partition()
+        .unwrap_or(mpd::DEFAULT_PARTITION)
+        .to_string();
+    let unmuted = server.take_unmuted_volume(&partition);
+    if volume > 0 {
+        server.set_unmuted_volume(&partition, volume);
+        mpd.set_volume(0).await?;
+    } else {
+        mpd.set_volume(unmuted.unwrap_or(100)).await?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,267 @@
+//! A WebSocket for remote controls. It pushes what changed in MPD as typed
+//! JSON messages, and accepts control messages in return, so clients don't
+//! have to poll or refetch HTML fragments.
+
+use actix_web::{get, rt, web, HttpRequest, HttpResponse};
+use actix_ws::AggregatedMessage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    auth, mpd,
+    routes::{controls, session::Session},
+};
+
+const SYSTEMS: &[&str] = &["playlist", "player", "options", "mixer"];
+
+/// A message sent to the client.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    /// The status fields that changed, or all of them in the first message
+    Status { status: Map<String, Value> },
+    /// Sent whenever another song becomes the current one
+    Song { song: Option<mpd::Song> },
+    /// The queue items that changed since the previous queue version, or the
+    /// whole queue in the first message. Items from `length` on were removed.
+    Queue {
+        version: u32,
+        length: u32,
+        changes: Vec<mpd::Song>,
+    },
+    /// A control message could not be parsed or was refused by MPD
+    Error { message: String },
+}
+
+/// A control message received from the client.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Action {
+    Play {
+        #[serde(default)]
+        position: Option<u32>,
+    },
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    /// Seeks to `time` seconds in the current song, like `POST /seek`
+    Seek {
+        time: f64,
+    },
+    /// Either an absolute `value` from 0 to 100, or a relative `change`
+    Volume {
+        #[serde(default)]
+        value: Option<u32>,
+        #[serde(default)]
+        change: Option<i32>,
+    },
+    Add {
+        path: String,
+        #[serde(default)]
+        next: bool,
+    },
+    Delete {
+        id: u32,
+    },
+    Move {
+        from: u32,
+        to: u32,
+    },
+    Clear,
+}
+
+impl Action {
+    async fn run(self, mpd: &mut mpd::Mpd) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Play { position } => {
+                let position = position.map(|position| position.to_string());
+                mpd.play(position.as_deref()).await?;
+            }
+            Self::Pause => _ = mpd.command("pause 1").await?,
+            Self::Stop => _ = mpd.command("stop").await?,
+            Self::Next => _ = mpd.command("next").await?,
+            Self::Previous => _ = mpd.command("previous").await?,
+            Self::Seek { time } => controls::seek(mpd, time, false, None).await?,
+            Self::Volume { value, change } => match (value, change) {
+                (Some(value), _) => mpd.set_volume(value).await?,
+                (None, Some(change)) => mpd.change_volume(change).await?,
+                (None, None) => {}
+            },
+            Self::Add { path, next } => {
+                let mut list = mpd.command_list();
+                if next {
+                    list.add_position(&path, "+0")?;
+                } else {
+                    list.add(&path);
+                }
+                list.run().await?;
+            }
+            Self::Delete { id } => _ = mpd.command(&format!("deleteid {id}")).await?,
+            Self::Move { from, to } => _ = mpd.command(&format!("move {from} {to}")).await?,
+            Self::Clear => _ = mpd.command("clear").await?,
+        }
+        Ok(())
+    }
+}
+
+/// What the client has been told so far, to only send what changed.
+#[derive(Default)]
+struct State {
+    status: Map<String, Value>,
+    song_id: Option<Option<u32>>,
+    queue_version: Option<u32>,
+}
+
+impl State {
+    async fn update(&mut self, mpd: &mut mpd::Mpd) -> mpd::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        let status = mpd.status().await?;
+
+        let Ok(Value::Object(fields)) = serde_json::to_value(&status) else {
+            unreachable!("status serializes to an object");
+        };
+        let changed: Map<_, _> = fields
+            .iter()
+            .filter(|(key, value)| self.status.get(*key) != Some(value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if !changed.is_empty() {
+            events.push(Event::Status { status: changed });
+        }
+        self.status = fields;
+
+        if self.song_id != Some(status.song_id) {
+            let song = mpd.command("currentsong").await?.into_song()?;
+            events.push(Event::Song { song });
+            self.song_id = Some(status.song_id);
+        }
+
+        if self.queue_version != Some(status.playlist) {
+            let changes = mpd.queue_changes(self.queue_version.unwrap_or(0)).await?;
+            events.push(Event::Queue {
+                version: status.playlist,
+                length: status.playlist_length,
+                changes,
+            });
+            self.queue_version = Some(status.playlist);
+        }
+
+        Ok(events)
+    }
+}
+
+/// Sends everything that changed, returning `false` once the client is gone.
+async fn send_update(session: &Session, state: &mut State, socket: &mut actix_ws::Session) -> bool {
+    let events = match session.mpd().await {
+        Ok(mut mpd) => state.update(&mut mpd).await,
+        Err(e) => Err(e),
+    };
+    let events = events.unwrap_or_else(|e| {
+        vec![Event::Error {
+            message: e.to_string(),
+        }]
+    });
+
+    for event in events {
+        let text = serde_json::to_string(&event).expect("events serialize to JSON");
+        if socket.text(text).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Parses and runs a control message, returning a message for the client if
+/// that failed.
+async fn run_action(session: &Session, text: &str) -> Result<(), String> {
+    let action: Action = serde_json::from_str(text).map_err(|e| format!("invalid message: {e}"))?;
+    let mut mpd = session.mpd().await.map_err(|e| e.to_string())?;
+    action.run(&mut mpd).await.map_err(|e| e.to_string())
+}
+
+#[get("/ws")]
+pub async fn get_ws(
+    session: Session,
+    user: Option<web::ReqData<auth::User>>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+    // Guests may watch, but messages are out of the middleware's sight
+    let read_only = user.is_some_and(|user| user.is_guest());
+    let mut idle = session.connect().await?;
+    let (response, mut socket, stream) = actix_ws::handle(&req, body)?;
+
+    let (tx, mut changes) = tokio::sync::mpsc::channel(10);
+    let server = session.server();
+    let idle_task = rt::spawn(async move {
+        loop {
+            let deleted = server.partition_deleted(idle.partition());
+            let result = tokio::select! {
+                result = idle.idle(SYSTEMS) => result,
+                () = deleted => idle.leave_partition().await.map(|()| Vec::new()),
+            };
+            if let Err(e) = result {
+                println!("Lost idle connection to MPD ({e}), reconnecting");
+                if idle.reconnect().await.is_err() {
+                    // Dropping the sender ends the WebSocket, making the client reconnect
+                    break;
+                }
+            }
+
+            if tx.send(()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    rt::spawn(async move {
+        let mut stream = stream.aggregate_continuations();
+        let mut state = State::default();
+
+        if send_update(&session, &mut state, &mut socket).await {
+            loop {
+                tokio::select! {
+                    change = changes.recv() => {
+                        let sent = match change {
+                            Some(()) => send_update(&session, &mut state, &mut socket).await,
+                            None => false,
+                        };
+                        if !sent {
+                            break;
+                        }
+                    }
+
+                    message = stream.recv() => match message {
+                        Some(Ok(AggregatedMessage::Text(text))) => {
+                            let result = if read_only {
+                                Err("guests cannot change anything".to_string())
+                            } else {
+                                run_action(&session, &text).await
+                            };
+                            if let Err(message) = result {
+                                let text = serde_json::to_string(&Event::Error { message })
+                                    .expect("events serialize to JSON");
+                                if socket.text(text).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                        Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                            if socket.pong(&bytes).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Ok(AggregatedMessage::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    },
+                }
+            }
+        }
+
+        idle_task.abort();
+        _ = socket.close(None).await;
+    });
+
+    Ok(response)
+}